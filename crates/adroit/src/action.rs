@@ -0,0 +1,510 @@
+use std::{ops::Range, path::Path, sync::Arc};
+
+use crate::{
+    compile::{FullModule, GraphImporter, Printer},
+    fetch::{builtin, builtins},
+    graph::{Data, Graph, Source, Syntax, Uri},
+    hint::unannotated,
+    lex::{lex, TokenId},
+    parse::{self, parse, Bind, Expr},
+    range::{bind_range, def_range, expr_range, import_range, param_range, ty_range},
+    rename::{apply, Edit},
+    symbol::{self, Symbol},
+    typecheck::{self, typecheck, TypeError},
+    util::Id,
+};
+
+/// A named set of edits to a module that fixes a problem in it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Action {
+    pub title: String,
+    pub edits: Vec<Edit>,
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+fn insert(offset: usize, text: String) -> Edit {
+    Edit {
+        range: offset..offset,
+        text,
+    }
+}
+
+/// Whether a definition's signature has all the annotations the typechecker requires.
+fn annotated(tree: &parse::Module, def: &parse::Def) -> bool {
+    let mut params = vec![];
+    for &param in &def.params {
+        unannotated(tree, param, &mut params);
+    }
+    params.is_empty() && def.ty.is_some()
+}
+
+/// Where a result annotation goes in a definition whose body starts at the given offset.
+fn result_offset(text: &str, body: usize) -> Option<usize> {
+    let eq = text[..body].rfind('=')?;
+    Some(text[..eq].trim_end().len())
+}
+
+/// Annotate every parameter and the result of a definition, by typechecking its body as a lambda
+/// in a copy of the module where no other definition depends on it.
+fn annotate_def(graph: &Graph, uri: &Uri, syn: &Syntax, id: parse::DefId) -> Option<Action> {
+    let (text, tokens, tree) = (&syn.src.text, &syn.toks, &syn.tree);
+    let def = tree.def(id);
+    let name = &text[tokens.get(def.name).byte_range()];
+    let mut edits = vec![];
+    for (i, other) in tree.defs().iter().enumerate() {
+        let other_id = parse::DefId::from_usize(i).unwrap();
+        if other_id == id {
+            continue;
+        }
+        edits.push(if annotated(tree, other) {
+            Edit {
+                range: expr_range(tokens, tree, other.body)?,
+                text: "undefined".to_owned(),
+            }
+        } else {
+            Edit {
+                range: def_range(tokens, tree, other_id)?,
+                text: String::new(),
+            }
+        });
+    }
+    let mut lambda = String::new();
+    for &param in &def.params {
+        match tree.param(param).bind {
+            Bind::Unit { .. } => lambda.push_str("()"),
+            _ => lambda.push_str(&format!("({})", &text[param_range(tokens, tree, param)?])),
+        }
+        if let (true, Some(ty)) = (param == *def.params.last()?, def.ty) {
+            lambda.push_str(&format!(": {}", &text[ty_range(tokens, tree, ty)?]));
+        }
+        lambda.push_str(" => ");
+    }
+    let start = expr_range(tokens, tree, def.body)?.start;
+    lambda.push_str(&format!("({})", &text[expr_range(tokens, tree, def.body)?]));
+    let types: Vec<&str> = (def.types.iter())
+        .map(|&ty| &text[tokens.get(ty).byte_range()])
+        .collect();
+    let types = match types.is_empty() {
+        true => String::new(),
+        false => format!("[{}]", types.join(", ")),
+    };
+    edits.push(Edit {
+        range: def_range(tokens, tree, id)?,
+        text: format!("def {name}{types}: () =\n  let probe = {lambda}\n  ()"),
+    });
+    edits.sort_by_key(|edit| edit.range.start);
+    let source = apply(text, &edits);
+    let probe_tokens = lex(&source).ok()?;
+    let probe_tree = parse(&probe_tokens).ok()?;
+    let deps = (graph.imports(uri).ok()?.into_iter())
+        .map(|import| match &graph.get(&import).data {
+            Data::Analyzed { sem, errs, .. } if errs.is_empty() => Some((import, Arc::clone(sem))),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let imports = deps.iter().map(|(_, sem)| sem.as_ref()).collect();
+    let (module, errs) = typecheck(&source, &probe_tokens, &probe_tree, imports);
+    if !errs.is_empty() {
+        return None;
+    }
+    let full = FullModule {
+        source: &source,
+        tokens: &probe_tokens,
+        tree: &probe_tree,
+        module: Arc::new(module),
+    };
+    let module = Arc::clone(&full.module);
+    let printer = Printer::new(full, GraphImporter { graph, deps: &deps });
+    // a type that isn't fully known can't be written down
+    let print = |ty| Some(printer.ty(ty).to_string()).filter(|ty| !ty.contains('_'));
+    let probe = (probe_tree.defs().iter())
+        .find(|def| source[probe_tokens.get(def.name).byte_range()] == *name)?;
+    let Expr::Let { val, .. } = probe_tree.expr(probe.body) else {
+        return None;
+    };
+    let mut edits = vec![];
+    let mut expr = val;
+    for &param in &def.params {
+        let Expr::Lambda {
+            param: inner, body, ..
+        } = probe_tree.expr(expr)
+        else {
+            return None;
+        };
+        let (mut names, mut inferred) = (vec![], vec![]);
+        unannotated(tree, param, &mut names);
+        unannotated(&probe_tree, inner, &mut inferred);
+        if names.len() != inferred.len() {
+            return None;
+        }
+        for (name, inferred) in names.into_iter().zip(inferred) {
+            let ty = print(module.val(module.param(inferred)).ty)?;
+            edits.push(insert(
+                bind_range(tokens, tree, name)?.end,
+                format!(": {ty}"),
+            ));
+        }
+        if def.ty.is_none() && param == *def.params.last()? {
+            let typecheck::Type::Func { dom: _, cod } = module.ty(module.val(module.expr(expr)).ty)
+            else {
+                return None;
+            };
+            edits.push(insert(
+                result_offset(text, start)?,
+                format!(": {}", print(cod)?),
+            ));
+        }
+        expr = body;
+    }
+    if def.params.is_empty() {
+        let ty = print(module.val(module.expr(expr)).ty)?;
+        edits.push(insert(result_offset(text, start)?, format!(": {ty}")));
+    }
+    edits.sort_by_key(|edit| edit.range.start);
+    Some(Action {
+        title: format!("Add type annotations to `{name}`"),
+        edits,
+    })
+}
+
+/// Annotate the unannotated `let` and lambda parameters in a range with their inferred types.
+fn annotate_params(graph: &Graph, uri: &Uri, range: &Range<usize>) -> Vec<Action> {
+    let Data::Analyzed { syn, sem, deps, .. } = &graph.get(uri).data else {
+        return vec![];
+    };
+    let (text, tokens, tree, module) = (&syn.src.text, &syn.toks, &syn.tree, sem.as_ref());
+    let full = FullModule {
+        source: text,
+        tokens,
+        tree,
+        module: Arc::clone(sem),
+    };
+    let printer = Printer::new(full, GraphImporter { graph, deps });
+    let mut actions = vec![];
+    for expr in tree.exprs() {
+        let (Expr::Let { param, .. } | Expr::Lambda { param, .. }) = *expr else {
+            continue;
+        };
+        let mut params = vec![];
+        unannotated(tree, param, &mut params);
+        for id in params {
+            let Some(bind) = bind_range(tokens, tree, id) else {
+                continue;
+            };
+            let ty = printer.ty(module.val(module.param(id)).ty).to_string();
+            if !overlaps(&bind, range) || ty.contains('_') {
+                continue;
+            }
+            let name = &text[bind.clone()];
+            // a lambda parameter without parentheses can't have an annotation
+            let edit = match (expr, id == param) {
+                (Expr::Lambda { .. }, true) => Edit {
+                    range: bind,
+                    text: format!("({name}: {ty})"),
+                },
+                _ => insert(bind.end, format!(": {ty}")),
+            };
+            actions.push(Action {
+                title: format!("Add type annotation to `{name}`"),
+                edits: vec![edit],
+            });
+        }
+    }
+    actions
+}
+
+/// Whether a module declares a definition with the given name.
+fn declares(syn: &Syntax, name: &str) -> bool {
+    (syn.tree.defs().iter()).any(|def| syn.src.text[syn.toks.get(def.name).byte_range()] == *name)
+}
+
+/// Import an undefined name from every known module that declares it.
+fn add_import(graph: &Graph, uri: &Uri, syn: &Syntax, name: &str) -> Vec<Action> {
+    let (text, tokens, tree) = (&syn.src.text, &syn.toks, &syn.tree);
+    let Ok(uris) = graph.imports(uri) else {
+        return vec![];
+    };
+    let mut modules: Vec<Uri> = (graph.nodes())
+        .filter(|&(other, _)| other != uri)
+        .filter(|&(_, node)| match &node.data {
+            Data::Parsed { syn } | Data::Analyzed { syn, .. } => declares(syn, name),
+            _ => false,
+        })
+        .map(|(other, _)| other.clone())
+        .collect();
+    for module in builtins() {
+        let other = Uri::resolve(graph.stdlib(), graph.stdlib(), module).unwrap();
+        if graph.nodes().any(|(node, _)| *node == other) {
+            continue;
+        }
+        let Ok(source) = builtin(Path::new(&format!("{module}.adroit"))) else {
+            continue;
+        };
+        let Ok(toks) = lex(source) else {
+            continue;
+        };
+        let Ok(tree) = parse(&toks) else {
+            continue;
+        };
+        let src = Source::new(source.to_owned());
+        if declares(&Syntax { src, toks, tree }, name) {
+            modules.push(other);
+        }
+    }
+    modules.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    let mut actions = vec![];
+    for other in modules {
+        let Some(path) = uri.import_name(graph.stdlib(), &other) else {
+            continue;
+        };
+        let edit = match uris.iter().position(|import| *import == other) {
+            Some(i) => {
+                let import = &tree.imports()[i];
+                match import.names.last() {
+                    Some(&last) => insert(tokens.get(last).byte_range().end, format!(", {name}")),
+                    None => {
+                        let end = tokens.get(import.module).byte_range().end;
+                        let use_end = text[end..].find("use").map_or(end, |i| end + i + 3);
+                        insert(use_end, format!(" {name}"))
+                    }
+                }
+            }
+            None => match tree.imports().last() {
+                Some(last) => insert(
+                    import_range(tokens, tree, last).end,
+                    format!("\nimport \"{path}\" use {name}"),
+                ),
+                None => insert(0, format!("import \"{path}\" use {name}\n\n")),
+            },
+        };
+        actions.push(Action {
+            title: format!("Import `{name}` from \"{path}\""),
+            edits: vec![edit],
+        });
+    }
+    actions
+}
+
+/// Remove each imported name in a range that nothing in the module refers to.
+fn remove_imports(graph: &Graph, uri: &Uri, syn: &Syntax, range: &Range<usize>) -> Vec<Action> {
+    let (text, tokens, tree) = (&syn.src.text, &syn.toks, &syn.tree);
+    let mut actions = vec![];
+    for import in tree.imports() {
+        let whole = import_range(tokens, tree, import);
+        if !overlaps(&whole, range) {
+            continue;
+        }
+        let names: Vec<Range<usize>> = (import.names.iter())
+            .map(|&name| tokens.get(name).byte_range())
+            .collect();
+        for (i, name) in names.iter().enumerate() {
+            let unused = symbol::resolve(graph, uri, |resolver| {
+                let Some((symbol @ Symbol::Def { .. }, _)) = resolver.find(name.start) else {
+                    return false;
+                };
+                (resolver.uses(symbol).iter()).all(|used| names.contains(used))
+            });
+            if unused != Some(true) {
+                continue;
+            }
+            let removed = if names.len() == 1 {
+                let end = match text[whole.end..].find('\n') {
+                    Some(i) => whole.end + i + 1,
+                    None => text.len(),
+                };
+                whole.start..end
+            } else if i + 1 < names.len() {
+                name.start..names[i + 1].start
+            } else {
+                names[i - 1].end..name.end
+            };
+            actions.push(Action {
+                title: format!("Remove unused import `{}`", &text[name.clone()]),
+                edits: vec![Edit {
+                    range: removed,
+                    text: String::new(),
+                }],
+            });
+        }
+    }
+    actions
+}
+
+/// The quick fixes available for a byte range in a module.
+pub fn actions(graph: &Graph, uri: &Uri, range: Range<usize>) -> Vec<Action> {
+    let Data::Analyzed { syn, errs, .. } = &graph.get(uri).data else {
+        return vec![];
+    };
+    let (text, tokens, tree) = (&syn.src.text, &syn.toks, &syn.tree);
+    let token = |id: TokenId| tokens.get(id).byte_range();
+    let mut actions = vec![];
+    for (i, def) in tree.defs().iter().enumerate() {
+        let id = parse::DefId::from_usize(i).unwrap();
+        let Some(whole) = def_range(tokens, tree, id) else {
+            continue;
+        };
+        if !annotated(tree, def) && overlaps(&whole, &range) {
+            actions.extend(annotate_def(graph, uri, syn, id));
+        }
+    }
+    actions.extend(annotate_params(graph, uri, &range));
+    for err in errs {
+        if let TypeError::Undefined { name } = *err {
+            if overlaps(&token(name), &range) {
+                actions.extend(add_import(graph, uri, syn, &text[token(name)]));
+            }
+        }
+    }
+    if errs.is_empty() {
+        actions.extend(remove_imports(graph, uri, syn, &range));
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The quick fixes at `cursor` in the first of the given modules, applied to its text.
+    fn fix_in(files: &[(&str, &str)], cursor: &str) -> Vec<(String, String)> {
+        let uri = |path: &str| Uri::from_file_path(path).unwrap();
+        let mut graph = Graph::new(Uri::from_directory_path("/std").unwrap());
+        for &(path, text) in files {
+            graph.make_root(uri(path));
+            graph.set_text(&uri(path), text.to_owned());
+        }
+        loop {
+            let pending = graph.pending();
+            if pending.is_empty() {
+                break;
+            }
+            for other in pending {
+                let path = other.to_file_path().unwrap();
+                let source = builtin(path.strip_prefix("/std").unwrap()).unwrap();
+                graph.set_text(&other, source.to_owned());
+            }
+        }
+        loop {
+            let analysis = graph.analysis();
+            if analysis.is_empty() {
+                break;
+            }
+            for job in analysis {
+                let (_, syn, deps) = &job;
+                let imports = deps.iter().map(|(_, dep)| dep.as_ref()).collect();
+                let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, imports);
+                graph.supply_semantic(job, module.into(), errs);
+            }
+        }
+        let (path, src) = files[0];
+        let offset = src.find(cursor).unwrap();
+        (actions(&graph, &uri(path), offset..offset).into_iter())
+            .map(|action| (action.title, apply(src, &action.edits)))
+            .collect()
+    }
+
+    fn fix(src: &str, cursor: &str) -> Vec<(String, String)> {
+        fix_in(&[("/a.adroit", src)], cursor)
+    }
+
+    #[test]
+    fn test_annotate_def() {
+        let src = "def g(x: Int): Int = x\n\ndef f(a, b: Int) =\n  let y = g(a)\n  y\n";
+        assert_eq!(
+            fix(src, "f("),
+            [(
+                "Add type annotations to `f`".to_owned(),
+                "def g(x: Int): Int = x\n\ndef f(a: Int, b: Int): Int =\n  let y = g(a)\n  y\n"
+                    .to_owned(),
+            )],
+        );
+        assert_eq!(fix("def f(a) = a\n", "f("), []);
+    }
+
+    #[test]
+    fn test_annotate_params() {
+        let src = "def f(a: Int): Int =\n  let g = x => a\n  g(a)\n";
+        assert_eq!(
+            fix(src, "x =>"),
+            [(
+                "Add type annotation to `x`".to_owned(),
+                "def f(a: Int): Int =\n  let g = (x: Int) => a\n  g(a)\n".to_owned(),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_add_import() {
+        let body = "def f(x: Float): Float = sqrt(x)\n";
+        assert_eq!(
+            fix(body, "sqrt"),
+            [(
+                "Import `sqrt` from \"math\"".to_owned(),
+                format!("import \"math\" use sqrt\n\n{body}"),
+            )],
+        );
+        assert_eq!(
+            fix(&format!("import \"math\" use pi\n\n{body}"), "sqrt"),
+            [(
+                "Import `sqrt` from \"math\"".to_owned(),
+                format!("import \"math\" use pi, sqrt\n\n{body}"),
+            )],
+        );
+        assert_eq!(
+            fix(&format!("import \"math\" use\n\n{body}"), "sqrt"),
+            [(
+                "Import `sqrt` from \"math\"".to_owned(),
+                format!("import \"math\" use sqrt\n\n{body}"),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_add_import_local() {
+        let src = "import \"math\" use pi\n\ndef f: Float = g(pi)\n";
+        let lib = "def g(x: Float): Float = x\n";
+        assert_eq!(
+            fix_in(&[("/a.adroit", src), ("/b.adroit", lib)], "g("),
+            [(
+                "Import `g` from \"./b\"".to_owned(),
+                "import \"math\" use pi\nimport \"./b\" use g\n\ndef f: Float = g(pi)\n".to_owned(),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_remove_imports() {
+        let body = "\ndef f(x: Float): Float = sqrt(x)\n";
+        assert_eq!(
+            fix(&format!("import \"math\" use pi, sqrt\n{body}"), "pi"),
+            [(
+                "Remove unused import `pi`".to_owned(),
+                format!("import \"math\" use sqrt\n{body}"),
+            )],
+        );
+        assert_eq!(
+            fix(&format!("import \"math\" use sqrt, pi\n{body}"), "pi"),
+            [(
+                "Remove unused import `pi`".to_owned(),
+                format!("import \"math\" use sqrt\n{body}"),
+            )],
+        );
+        assert_eq!(
+            fix(
+                &format!("import \"math\" use pi\nimport \"math\" use sqrt\n{body}"),
+                "pi"
+            ),
+            [(
+                "Remove unused import `pi`".to_owned(),
+                format!("import \"math\" use sqrt\n{body}"),
+            )],
+        );
+        assert_eq!(
+            fix(&format!("import \"math\" use sqrt\n{body}"), "sqrt"),
+            []
+        );
+    }
+}
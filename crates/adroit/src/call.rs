@@ -0,0 +1,176 @@
+use std::ops::Range;
+
+use crate::{
+    compile::{GraphImporter, Importer},
+    graph::{Data, Graph, Uri},
+    outline::{items, Item, Kind},
+    parse::{self, Expr},
+    range::def_range,
+    symbol::{self, Symbol},
+    typecheck::Src,
+    util::Id,
+};
+
+/// A top-level definition in some module in the graph.
+pub type Def = (Uri, parse::DefId);
+
+/// A definition along with the ranges of every call between it and another one.
+pub type Calls = (Def, Vec<Range<usize>>);
+
+/// Every call to a top-level definition in a module, as the definition containing the call, the
+/// definition being called, and the range of its name in the call.
+fn calls(graph: &Graph, uri: &Uri) -> Vec<(parse::DefId, Def, Range<usize>)> {
+    let Data::Analyzed { syn, sem, deps, .. } = &graph.get(uri).data else {
+        return vec![];
+    };
+    let importer = GraphImporter { graph, deps };
+    let (tokens, tree) = (&syn.toks, &syn.tree);
+    let defs: Vec<Range<usize>> = (0..tree.defs().len())
+        .map(|i| def_range(tokens, tree, parse::DefId::from_usize(i).unwrap()).unwrap())
+        .collect();
+    let mut calls = vec![];
+    for expr in tree.exprs() {
+        let (Expr::Apply { func, arg: _ } | Expr::Map { func, arg: _ }) = *expr else {
+            continue;
+        };
+        let mut head = func;
+        while let Expr::Inst { val: inner, ty: _ } | Expr::Paren { inner } = tree.expr(head) {
+            head = inner;
+        }
+        let Expr::Name { name } = tree.expr(head) else {
+            continue;
+        };
+        let callee = match sem.origin(head) {
+            Src::Def { id } => (uri.clone(), id),
+            // a definition from an outdated import might not be there anymore
+            Src::Import { src, id } if importer.import(src).is_some() => {
+                (deps[src.to_usize()].0.clone(), id)
+            }
+            _ => continue,
+        };
+        let range = tokens.get(name).byte_range();
+        let Some(caller) = defs.iter().position(|def| def.contains(&range.start)) else {
+            continue;
+        };
+        calls.push((parse::DefId::from_usize(caller).unwrap(), callee, range));
+    }
+    // inner expressions come first in the tree, but the order in the source is easier to follow
+    calls.sort_by_key(|(_, _, range)| range.start);
+    calls
+}
+
+/// Add a call to the group for its definition, keeping groups in the order they first appear.
+fn group(groups: &mut Vec<Calls>, def: Def, range: Range<usize>) {
+    match groups.iter_mut().find(|(other, _)| *other == def) {
+        Some((_, ranges)) => ranges.push(range),
+        None => groups.push((def, vec![range])),
+    }
+}
+
+/// The top-level definition named at a byte offset in a module, wherever it is declared.
+pub fn def_at(graph: &Graph, uri: &Uri, offset: usize) -> Option<Def> {
+    let (symbol, _) = symbol::resolve(graph, uri, |resolver| resolver.find(offset))??;
+    match symbol {
+        Symbol::Def { src: None, id } => Some((uri.clone(), id)),
+        Symbol::Def { src: Some(src), id } => {
+            Some((graph.imports(uri).ok()?.swap_remove(src.to_usize()), id))
+        }
+        _ => None,
+    }
+}
+
+/// The outline item for a top-level definition.
+pub fn item(graph: &Graph, (uri, id): &Def) -> Option<Item> {
+    let (Data::Parsed { syn } | Data::Analyzed { syn, .. }) = &graph.get(uri).data else {
+        return None;
+    };
+    let name = syn.toks.get(syn.tree.def(*id).name).byte_range();
+    (items(graph, uri)?.into_iter()).find(|item| item.kind == Kind::Def && item.selection == name)
+}
+
+/// Every definition that calls the given one, with the ranges of those calls in its own module.
+pub fn incoming(graph: &Graph, def: &Def) -> Vec<Calls> {
+    let mut uris: Vec<&Uri> = graph.nodes().map(|(uri, _)| uri).collect();
+    uris.sort_by_key(|uri| uri.as_str());
+    let mut groups = vec![];
+    for uri in uris {
+        for (caller, callee, range) in calls(graph, uri) {
+            if callee == *def {
+                group(&mut groups, (uri.clone(), caller), range);
+            }
+        }
+    }
+    groups
+}
+
+/// Every definition that the given one calls, with the ranges of those calls in the given one.
+pub fn outgoing(graph: &Graph, (uri, id): &Def) -> Vec<Calls> {
+    let mut groups = vec![];
+    for (caller, callee, range) in calls(graph, uri) {
+        if caller == *id {
+            group(&mut groups, callee, range);
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::typecheck::typecheck;
+
+    use super::*;
+
+    fn uri(path: &str) -> Uri {
+        Uri::from_file_path(path).unwrap()
+    }
+
+    fn graph(files: &[(&str, &str)]) -> Graph {
+        let mut graph = Graph::new(Uri::from_directory_path("/std").unwrap());
+        for &(path, text) in files {
+            graph.make_root(uri(path));
+            graph.set_text(&uri(path), text.to_owned());
+        }
+        loop {
+            let analysis = graph.analysis();
+            if analysis.is_empty() {
+                break;
+            }
+            for job in analysis {
+                let (_, syn, deps) = &job;
+                let (module, errs) = typecheck(
+                    &syn.src.text,
+                    &syn.toks,
+                    &syn.tree,
+                    deps.iter().map(|(_, dep)| dep.as_ref()).collect(),
+                );
+                graph.supply_semantic(job, Arc::new(module), errs);
+            }
+        }
+        graph
+    }
+
+    fn names(graph: &Graph, groups: Vec<Calls>) -> Vec<(String, usize)> {
+        (groups.into_iter())
+            .map(|(def, ranges)| (item(graph, &def).unwrap().name, ranges.len()))
+            .collect()
+    }
+
+    #[test]
+    fn test_calls() {
+        let lib = "def sq(x: Float): Float = x * x\n\ndef id[T](x: T): T = x\n";
+        let main = "import \"./lib\" use sq, id\n\ndef f(x: Float): Float = sq(id[Float](x)) + (sq)(x)\n\ndef g(x: Float): Float = f(x)\n";
+        let graph = graph(&[("/lib.adroit", lib), ("/main.adroit", main)]);
+        let sq = def_at(&graph, &uri("/main.adroit"), main.find("sq(").unwrap()).unwrap();
+        assert_eq!(
+            sq,
+            (uri("/lib.adroit"), parse::DefId::from_usize(0).unwrap())
+        );
+        assert_eq!(names(&graph, incoming(&graph, &sq)), [("f".to_owned(), 2)]);
+        let f = def_at(&graph, &uri("/main.adroit"), main.find("f(").unwrap()).unwrap();
+        let calls = names(&graph, outgoing(&graph, &f));
+        assert_eq!(calls, [("sq".to_owned(), 2), ("id".to_owned(), 1)]);
+        assert_eq!(names(&graph, incoming(&graph, &f)), [("g".to_owned(), 1)]);
+    }
+}
@@ -1,18 +1,23 @@
-use std::{collections::HashMap, io, marker::PhantomData, ops::Range, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap, env, fs, io, marker::PhantomData, ops::Range, path::PathBuf, sync::Arc,
+};
 
-use ariadne::{Cache, Color, Label, Report, ReportBuilder, ReportKind, Source};
+use ariadne::{Cache, Color, FnCache, Label, Report, ReportBuilder, ReportKind, Source};
 use clap::{Parser, Subcommand};
 use itertools::Itertools;
+use line_index::LineCol;
 use serde::Serialize;
 
 use crate::{
+    call,
     compile::{FullModule, GraphImporter, Printer},
-    fetch::fetch,
+    fetch::{fetch, importers},
     graph::{Analysis, Data, Graph, Syntax, Uri},
     lex::Tokens,
     lsp::language_server,
     parse::{self, ParseError},
     pprint::pprint,
+    rename::{apply, rename},
     typecheck,
     util::{Diagnostic, Emitter},
 };
@@ -49,28 +54,42 @@ impl<'a, C: Cache<&'a str>> AriadneEmitter<'a, C> {
             phantom: PhantomData,
         }
     }
-}
 
-impl<'a, C: Cache<&'a str>> Emitter<(&'a str, Range<usize>)> for AriadneEmitter<'a, C> {
-    fn diagnostic(
+    fn report(
         &mut self,
+        kind: ReportKind<'a>,
+        color: Color,
         span: (&'a str, Range<usize>),
         message: impl ToString,
-    ) -> impl Diagnostic<(&'a str, Range<usize>)> {
+    ) -> AriadneDiagnostic<'a, '_, C> {
         let (path, range) = span.clone();
         AriadneDiagnostic {
             cache: &mut self.cache,
-            builder: Report::build(ReportKind::Error, path, range.start)
+            builder: Report::build(kind, path, range.start)
                 .with_message(&self.message)
-                .with_label(
-                    Label::new(span)
-                        .with_color(Color::Red)
-                        .with_message(message),
-                ),
+                .with_label(Label::new(span).with_color(color).with_message(message)),
         }
     }
 }
 
+impl<'a, C: Cache<&'a str>> Emitter<(&'a str, Range<usize>)> for AriadneEmitter<'a, C> {
+    fn diagnostic(
+        &mut self,
+        span: (&'a str, Range<usize>),
+        message: impl ToString,
+    ) -> impl Diagnostic<(&'a str, Range<usize>)> {
+        self.report(ReportKind::Error, Color::Red, span, message)
+    }
+
+    fn info(
+        &mut self,
+        span: (&'a str, Range<usize>),
+        message: impl ToString,
+    ) -> impl Diagnostic<(&'a str, Range<usize>)> {
+        self.report(ReportKind::Advice, Color::Cyan, span, message)
+    }
+}
+
 #[derive(Debug)]
 struct AriadneDiagnostic<'a, 'b, C: Cache<&'a str>> {
     cache: &'b mut C,
@@ -89,6 +108,11 @@ impl<'a, C: Cache<&'a str>> Diagnostic<(&'a str, Range<usize>)>
         self
     }
 
+    fn note(mut self, message: impl ToString) -> Self {
+        self.builder.set_note(message);
+        self
+    }
+
     fn finish(self) {
         self.builder.finish().eprint(self.cache).unwrap();
     }
@@ -128,26 +152,43 @@ fn analyze(graph: &mut Graph, job: Analysis) -> Result<(), ()> {
         deps.iter().map(|(_, dep)| dep.as_ref()).collect(),
     );
     let sem = Arc::new(module);
-    if errs.is_empty() {
-        graph.supply_semantic(job, sem, errs);
-        Ok(())
-    } else {
+    let holes = !sem.holes().is_empty();
+    if !errs.is_empty() || holes {
         let uri_str = uri.as_str();
-        let uris: Vec<Uri> = deps.iter().map(|(import, _)| import.clone()).collect();
         let full = FullModule {
             source: &syn.src.text,
             tokens: &syn.toks,
             tree: &syn.tree,
-            module: sem,
+            module: Arc::clone(&sem),
         };
-        let printer = Printer::new(full, GraphImporter { graph, uris: &uris });
+        let mut sources = HashMap::from([(uri_str, Source::from(syn.src.text.as_str()))]);
+        for (dep, _) in deps.iter() {
+            if let Data::Analyzed { syn, .. } = &graph.get(dep).data {
+                sources.insert(dep.as_str(), Source::from(syn.src.text.as_str()));
+            }
+        }
+        let printer = Printer::new(full, GraphImporter { graph, deps });
         let mut emitter = AriadneEmitter::new(
-            (uri_str, Source::from(&syn.src.text)),
-            "failed to typecheck",
+            FnCache::new(
+                (|uri: &&str| Err(Box::new(format!("unknown source: {uri}")) as _)) as fn(&_) -> _,
+            )
+            .with_sources(sources),
+            if errs.is_empty() {
+                "found typed holes"
+            } else {
+                "failed to typecheck"
+            },
         );
-        for err in errs {
+        for &err in &errs {
             printer.emit_type_error(&mut emitter, uri_str, err);
         }
+        printer.emit_holes(&mut emitter, uri_str);
+    }
+    // holes are only there to ask what fits, so they don't stop anything else
+    if errs.is_empty() {
+        graph.supply_semantic(job, sem, errs);
+        Ok(())
+    } else {
         Err(())
     }
 }
@@ -171,7 +212,36 @@ fn exhaust(graph: &mut Graph) -> Result<(), ()> {
             analyze(graph, job)?;
         }
     }
-    Ok(())
+    stuck(graph)
+}
+
+/// Report the import cycles that keep modules from being analyzed, failing if there are any.
+fn stuck(graph: &Graph) -> Result<(), ()> {
+    // every error so far stops right away, so anything left unanalyzed is stuck on a cycle
+    let mut stuck: Vec<(&Uri, &Arc<Syntax>)> = graph
+        .nodes()
+        .filter_map(|(uri, node)| match &node.data {
+            Data::Parsed { syn } => Some((uri, syn)),
+            _ => None,
+        })
+        .collect();
+    stuck.sort_by_key(|&(uri, _)| uri.as_str());
+    for &(uri, syn) in &stuck {
+        let uri_str = uri.as_str();
+        for (import, dep) in syn.tree.imports().iter().zip(graph.imports(uri)?) {
+            if let Some(cycle) = graph.cycle(uri, &dep) {
+                let range = syn.toks.get(import.module).byte_range();
+                AriadneEmitter::new((uri_str, Source::from(&syn.src.text)), "failed to import")
+                    .diagnostic((uri_str, range), cycle.message(graph.stdlib()))
+                    .finish();
+            }
+        }
+    }
+    if stuck.is_empty() {
+        Ok(())
+    } else {
+        Err(())
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -205,6 +275,21 @@ enum Commands {
 
     /// Start a language server over stdio
     Lsp,
+
+    /// Rename the symbol at a position, updating every module under the current directory that
+    /// uses it
+    Rename {
+        file: PathBuf,
+
+        /// Line number of the symbol, starting from 1
+        line: u32,
+
+        /// Column number of the symbol in bytes, starting from 1
+        column: u32,
+
+        /// The new name
+        name: String,
+    },
 }
 
 pub fn cli() -> Result<(), ()> {
@@ -222,28 +307,71 @@ pub fn cli() -> Result<(), ()> {
             let modules = graph
                 .nodes()
                 .map(|(uri, node)| match &node.data {
-                    Data::Analyzed { syn, sem, errs } => {
-                        assert!(errs.is_empty());
+                    Data::Analyzed { syn, sem, deps, .. } => {
                         let full = FullNode {
                             source: &syn.src.text,
                             tokens: &syn.toks,
                             tree: &syn.tree,
-                            imports: graph.imports(uri)?,
+                            imports: deps.iter().map(|(dep, _)| dep.clone()).collect(),
                             module: sem.clone(),
                         };
-                        Ok((uri, full))
+                        Some((uri, full))
                     }
-                    _ => Err(()),
+                    _ => None,
                 })
-                .collect::<Result<HashMap<&Uri, FullNode>, ()>>()
-                .map_err(|()| {
-                    eprintln!("cyclic import");
-                })?;
+                .collect::<Option<HashMap<&Uri, FullNode>>>();
+            let Some(modules) = modules else {
+                return stuck(&graph).and(Err(()));
+            };
             serde_json::to_writer(io::stdout(), &Modules { root, modules })
                 .map_err(|err| eprintln!("error serializing modules: {err}"))?;
             println!();
             Ok(())
         }
         Commands::Lsp => language_server(stdlib()),
+        Commands::Rename {
+            file,
+            line,
+            column,
+            name,
+        } => {
+            let (mut graph, root) = rooted_graph(file)?;
+            exhaust(&mut graph)?;
+            let offset = match &graph.get(&root).data {
+                Data::Analyzed { syn, .. } => {
+                    let linecol = LineCol {
+                        line: line.saturating_sub(1),
+                        col: column.saturating_sub(1),
+                    };
+                    syn.src.lines.offset(linecol)
+                }
+                _ => None,
+            }
+            .ok_or_else(|| eprintln!("no position {line}:{column} in module"))?;
+            // nothing loaded so far imports the file, so its importers have to be found on disk
+            if let Some((home, _)) = call::def_at(&graph, &root, offset.into()) {
+                let dir = env::current_dir()
+                    .and_then(|dir| dir.canonicalize())
+                    .map_err(|err| eprintln!("error finding current directory: {err}"))?;
+                for (uri, text) in importers(&[dir], graph.stdlib(), &home) {
+                    if graph.nodes().all(|(other, _)| *other != uri) {
+                        graph.make_root(uri.clone());
+                        graph.set_text(&uri, text);
+                    }
+                }
+                exhaust(&mut graph)?;
+            }
+            let changes = rename(&graph, &root, offset.into(), &name)
+                .map_err(|err| eprintln!("error renaming: {err}"))?;
+            for (uri, edits) in changes {
+                let Data::Analyzed { syn, .. } = &graph.get(&uri).data else {
+                    unreachable!();
+                };
+                let path = uri.to_file_path().unwrap();
+                fs::write(&path, apply(&syn.src.text, &edits))
+                    .map_err(|err| eprintln!("error writing {}: {err}", path.display()))?;
+            }
+            Ok(())
+        }
     }
 }
@@ -21,31 +21,38 @@ pub struct FullModule<'a> {
     pub module: Arc<typecheck::Module>,
 }
 
-pub trait Importer {
-    fn import(&self, id: typecheck::ImportId) -> FullModule<'_>;
+pub trait Importer<'a> {
+    /// The imported module, if it's still the one this module was typechecked against.
+    fn import(&self, id: typecheck::ImportId) -> Option<FullModule<'a>>;
+
+    /// The path used in spans that point into the imported module.
+    fn path(&self, id: typecheck::ImportId) -> &'a str;
 }
 
 #[derive(Clone, Debug)]
 pub struct GraphImporter<'a> {
     pub graph: &'a Graph,
-    pub uris: &'a [Uri],
+    pub deps: &'a [(Uri, Arc<typecheck::Module>)],
 }
 
-impl Importer for GraphImporter<'_> {
-    fn import(&self, id: ImportId) -> FullModule<'_> {
-        match &self.graph.get(&self.uris[id.to_usize()]).data {
-            Data::Analyzed { syn, sem, errs } => {
-                assert!(errs.is_empty());
-                FullModule {
-                    source: &syn.src.text,
-                    tokens: &syn.toks,
-                    tree: &syn.tree,
-                    module: Arc::clone(sem),
-                }
-            }
-            _ => unreachable!(),
+impl<'a> Importer<'a> for GraphImporter<'a> {
+    fn import(&self, id: ImportId) -> Option<FullModule<'a>> {
+        let (uri, before) = &self.deps[id.to_usize()];
+        match &self.graph.get(uri).data {
+            // an import that changed since can't be used to explain this module's types
+            Data::Analyzed { syn, sem, .. } if Arc::ptr_eq(before, sem) => Some(FullModule {
+                source: &syn.src.text,
+                tokens: &syn.toks,
+                tree: &syn.tree,
+                module: Arc::clone(sem),
+            }),
+            _ => None,
         }
     }
+
+    fn path(&self, id: ImportId) -> &'a str {
+        self.deps[id.to_usize()].0.as_str()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -54,7 +61,7 @@ pub struct Printer<'a, I> {
     import: I,
 }
 
-impl<'a, I: Clone + Importer> Printer<'a, I> {
+impl<'a, I: Clone + Importer<'a>> Printer<'a, I> {
     pub fn new(full: FullModule<'a>, import: I) -> Self {
         Self { full, import }
     }
@@ -83,18 +90,53 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
         expr_range(self.full.tokens, self.full.tree, id).unwrap()
     }
 
-    pub fn ty(&self, id: typecheck::TypeId) -> Type<'_, I> {
+    fn site_range(&self, site: Option<typecheck::Site>) -> Range<usize> {
+        use typecheck::Site::*;
+        match site {
+            Some(Import { name }) => self.token_range(name),
+            Some(Def { id }) => self.token_range(self.full.tree.def(id).name),
+            Some(Type { id }) => self.ty_range(id),
+            Some(Param { id }) => self.param_range(id),
+            Some(Expr { id }) => self.expr_range(id),
+            // the module as a whole ran out, so point at its start
+            None => 0..0,
+        }
+    }
+
+    /// The token in an `import` that brings the given imported definition into scope.
+    fn import_name(&self, src: ImportId, id: parse::DefId) -> Option<TokenId> {
+        let module = self.import.import(src)?.module;
+        self.full.tree.imports()[src.to_usize()]
+            .names
+            .iter()
+            .copied()
+            .find(|&name| module.export(&self.full.source[self.token_range(name)]) == Some(id))
+    }
+
+    /// The span of an imported definition's signature, in the module that defines it.
+    fn import_sig(&self, src: ImportId, id: parse::DefId) -> Option<(&'a str, Range<usize>)> {
+        let full = self.import.import(src)?;
+        let def = full.tree.def(id);
+        let name = full.tokens.get(def.name).byte_range();
+        let end = match def.ty {
+            Some(ty) => ty_range(full.tokens, full.tree, ty).unwrap().end,
+            None => name.end,
+        };
+        Some((self.import.path(src), name.start..end))
+    }
+
+    pub fn ty(&self, id: typecheck::TypeId) -> Type<'a, I> {
         Type {
             printer: self.clone(),
             id,
         }
     }
 
-    fn param_ty(&self, id: parse::ParamId) -> Type<'_, I> {
+    fn param_ty(&self, id: parse::ParamId) -> Type<'a, I> {
         self.ty(self.full.module.val(self.full.module.param(id)).ty)
     }
 
-    fn expr_ty(&self, id: parse::ExprId) -> Type<'_, I> {
+    fn expr_ty(&self, id: parse::ExprId) -> Type<'a, I> {
         self.ty(self.full.module.val(self.full.module.expr(id)).ty)
     }
 
@@ -104,20 +146,28 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
             Unknown { id: _ } => write!(w, "_")?,
             Scalar { id: _ } => write!(w, "_")?,
             Vector { id: _, scalar: _ } => write!(w, "_")?,
+            // what number literals default to if nothing else decides their precision
+            IntLit { id: _ } => write!(w, "Int")?,
+            FloatLit { id: _ } => write!(w, "Float")?,
             Fragment => panic!("fragment type should not be printed"),
             Var { src, def } => {
                 let full = match src {
                     Some(id) => self.import.import(id),
-                    None => self.full.clone(),
+                    None => Some(self.full.clone()),
                 };
-                write!(w, "{}", &full.source[full.tokens.get(def).byte_range()])?;
+                match full {
+                    Some(full) => write!(w, "{}", &full.source[full.tokens.get(def).byte_range()])?,
+                    None => write!(w, "_")?,
+                }
             }
             Poly { var, inner } => {
                 write!(w, "{} => {}", self.ty(var), self.ty(inner))?;
             }
             Unit => write!(w, "()")?,
             Int => write!(w, "Int")?,
+            Int32 => write!(w, "Int32")?,
             Float => write!(w, "Float")?,
+            Float32 => write!(w, "Float32")?,
             Prod { fst, snd } => {
                 if let Prod { .. } | Sum { .. } | Func { .. } = self.get_ty(fst) {
                     write!(w, "({})", self.ty(fst))
@@ -186,12 +236,43 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
         emitter: &mut impl Emitter<(&'a str, Range<usize>)>,
         path: &'a str,
         err: typecheck::TypeError,
+    ) {
+        let note = err.size().map(|(left, right)| {
+            format!(
+                "array index types `{}` and `{}` differ even when regrouped",
+                self.ty(left),
+                self.ty(right),
+            )
+        });
+        self.emit(&mut Noted { emitter, note }, path, err)
+    }
+
+    fn emit(
+        &self,
+        emitter: &mut impl Emitter<(&'a str, Range<usize>)>,
+        path: &'a str,
+        err: typecheck::TypeError,
     ) {
         use typecheck::TypeError::*;
         match err {
-            TooManyImports => todo!("too many imports"),
-            TooManyTypes => todo!("too many types"),
-            TooManyFields => todo!("too many fields"),
+            TooManyImports { module } => emitter
+                .diagnostic(
+                    (path, self.token_range(module)),
+                    "module has too many imports",
+                )
+                .finish(),
+            TooManyTypes { site } => emitter
+                .diagnostic(
+                    (path, self.site_range(site)),
+                    "module has too many distinct types",
+                )
+                .finish(),
+            TooManyFields { site } => emitter
+                .diagnostic(
+                    (path, self.site_range(site)),
+                    "module has too many distinct field names",
+                )
+                .finish(),
             Undefined { name } => emitter
                 .diagnostic((path, self.token_range(name)), "undefined")
                 .finish(),
@@ -201,7 +282,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
             Dom { name } | Cod { name } => emitter
                 .diagnostic((path, self.token_range(name)), "untyped")
                 .finish(),
-            Param { id } => emitter
+            Param { id, size: _ } => emitter
                 .diagnostic(
                     (path, self.bind_range(id)),
                     format!("inferred type: `{}`", self.param_ty(id)),
@@ -211,7 +292,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     "does not match the given type",
                 )
                 .finish(),
-            Elem { id } => match self.full.tree.expr(id) {
+            Elem { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Elem { array, index } => emitter
                     .diagnostic(
                         (path, self.expr_range(index)),
@@ -231,42 +312,81 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     m += 1;
                     id = val;
                 }
-                let mut v = self.full.module.expr(id);
-                while let typecheck::Src::Inst { val, ty: _ } = self.full.module.val(v).src {
-                    v = val;
-                }
-                match self.full.module.val(v).src {
+                match self.full.module.origin(id) {
                     typecheck::Src::Def { id } => {
                         let def = self.full.tree.def(id);
                         let n = def.types.len();
                         emitter
-                            .diagnostic(
-                                (path, range),
-                                format!("{m} type arguments is {} too many", m - n),
-                            )
+                            .diagnostic((path, range), too_many_type_args(m, n))
                             .related(
                                 (path, self.token_range(def.name)),
-                                format!("function only takes {n} type parameters"),
+                                format!("function only takes {}", type_params(n)),
                             )
                             .finish()
                     }
-                    _ => todo!("too many type arguments for imported function"),
+                    typecheck::Src::Import { src, id } => {
+                        let (Some(full), Some(name), Some(sig)) = (
+                            self.import.import(src),
+                            self.import_name(src, id),
+                            self.import_sig(src, id),
+                        ) else {
+                            return emitter
+                                .diagnostic((path, range), "too many type arguments")
+                                .finish();
+                        };
+                        let n = full.tree.def(id).types.len();
+                        emitter
+                            .diagnostic((path, range), too_many_type_args(m, n))
+                            .related(
+                                (path, self.token_range(name)),
+                                format!("imported function only takes {}", type_params(n)),
+                            )
+                            .related(sig, "function declared here")
+                            .finish()
+                    }
+                    typecheck::Src::Param { id } => emitter
+                        .diagnostic((path, range), too_many_type_args(m, 0))
+                        .related(
+                            (path, self.bind_range(id)),
+                            "local variable takes no type parameters",
+                        )
+                        .finish(),
+                    typecheck::Src::Expr { .. } | typecheck::Src::Inst { .. } => emitter
+                        .diagnostic((path, range), too_many_type_args(m, 0))
+                        .finish(),
                 }
             }
-            Apply { id } => match self.full.tree.expr(id) {
-                parse::Expr::Apply { func, arg } => emitter
-                    .diagnostic(
-                        (path, self.expr_range(arg)),
-                        format!("argument type does not match: `{}`", self.expr_ty(arg)),
-                    )
-                    .related(
-                        (path, self.expr_range(func)),
-                        format!("function type: `{}`", self.expr_ty(func)),
-                    )
-                    .finish(),
+            InstVal { id } => emitter
+                .diagnostic(
+                    (path, self.expr_range(id)),
+                    "type arguments are only allowed when applying a function",
+                )
+                .finish(),
+            Apply { id, size: _ } => match self.full.tree.expr(id) {
+                parse::Expr::Apply { func, arg } => {
+                    let diagnostic = emitter
+                        .diagnostic(
+                            (path, self.expr_range(arg)),
+                            format!("argument type does not match: `{}`", self.expr_ty(arg)),
+                        )
+                        .related(
+                            (path, self.expr_range(func)),
+                            format!("function type: `{}`", self.expr_ty(func)),
+                        );
+                    let sig = match self.full.module.origin(func) {
+                        typecheck::Src::Import { src, id } => self.import_sig(src, id),
+                        _ => None,
+                    };
+                    match sig {
+                        Some(sig) => diagnostic
+                            .related(sig, "function type declared here")
+                            .finish(),
+                        None => diagnostic.finish(),
+                    }
+                }
                 _ => unreachable!(),
             },
-            MapLhs { id } => match self.full.tree.expr(id) {
+            MapLhs { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Map { func, arg: _ } => emitter
                     .diagnostic(
                         (path, self.expr_range(func)),
@@ -275,7 +395,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            MapRhs { id } => match self.full.tree.expr(id) {
+            MapRhs { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Map { func, arg } => emitter
                     .diagnostic(
                         (path, self.expr_range(arg)),
@@ -294,7 +414,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            Let { id } => match self.full.tree.expr(id) {
+            Let { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Let {
                     param,
                     val,
@@ -314,7 +434,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            Index { id } => match self.full.tree.expr(id) {
+            Index { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Index {
                     name: _,
                     val,
@@ -327,7 +447,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            Neg { id } => match self.full.tree.expr(id) {
+            Neg { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Unary { op: _, arg } => emitter
                     .diagnostic(
                         (path, self.expr_range(arg)),
@@ -336,7 +456,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            ElemLhs { id } | DivLhs { id } => match self.full.tree.expr(id) {
+            ElemLhs { id, size: _ } | DivLhs { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Binary { lhs, op: _, rhs: _ } => emitter
                     .diagnostic(
                         (path, self.expr_range(lhs)),
@@ -345,7 +465,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            ElemRhs { id } => match self.full.tree.expr(id) {
+            ElemRhs { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Binary { lhs, op: _, rhs } => emitter
                     .diagnostic(
                         (path, self.expr_range(rhs)),
@@ -361,7 +481,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            MulLhs { id } => match self.full.tree.expr(id) {
+            MulLhs { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Binary { lhs, op: _, rhs: _ } => emitter
                     .diagnostic(
                         (path, self.expr_range(lhs)),
@@ -370,7 +490,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            MulRhs { id } => match self.full.tree.expr(id) {
+            MulRhs { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Binary { lhs, op: _, rhs } => emitter
                     .diagnostic(
                         (path, self.expr_range(rhs)),
@@ -383,7 +503,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            DivRhs { id } => match self.full.tree.expr(id) {
+            DivRhs { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Binary { lhs, op: _, rhs } => emitter
                     .diagnostic(
                         (path, self.expr_range(rhs)),
@@ -396,7 +516,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            Lambda { id } => match self.full.tree.expr(id) {
+            Lambda { id, size: _ } => match self.full.tree.expr(id) {
                 parse::Expr::Lambda { param: _, ty, body } => emitter
                     .diagnostic(
                         (path, self.expr_range(body)),
@@ -409,7 +529,7 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
                     .finish(),
                 _ => unreachable!(),
             },
-            Def { id } => {
+            Def { id, size: _ } => {
                 let &parse::Def { ty, body, .. } = self.full.tree.def(id);
                 emitter
                     .diagnostic(
@@ -453,6 +573,70 @@ impl<'a, I: Clone + Importer> Printer<'a, I> {
             }
         }
     }
+
+    /// Show the type expected at each hole, along with the bindings in scope that fit it.
+    pub fn emit_holes(&self, emitter: &mut impl Emitter<(&'a str, Range<usize>)>, path: &'a str) {
+        for id in self.full.module.holes() {
+            let mut diagnostic = emitter.info(
+                (path, self.expr_range(id)),
+                format!("hole of type: `{}`", self.expr_ty(id)),
+            );
+            for &v in self.full.module.hole(id).unwrap_or_default() {
+                let typecheck::Val { ty, src } = self.full.module.val(v);
+                let range = match src {
+                    typecheck::Src::Param { id } => self.bind_range(id),
+                    typecheck::Src::Def { id } => self.token_range(self.full.tree.def(id).name),
+                    typecheck::Src::Import { src, id } => match self.import_name(src, id) {
+                        Some(name) => self.token_range(name),
+                        None => continue,
+                    },
+                    typecheck::Src::Expr { .. } | typecheck::Src::Inst { .. } => {
+                        unreachable!("hole fits should be bindings")
+                    }
+                };
+                let name = &self.full.source[range.clone()];
+                diagnostic =
+                    diagnostic.related((path, range), format!("fits: `{name}: {}`", self.ty(ty)));
+            }
+            diagnostic.finish()
+        }
+    }
+}
+
+/// Explains that `m` type arguments are more than the `n` type parameters something takes.
+fn too_many_type_args(m: usize, n: usize) -> String {
+    match m {
+        1 => format!("1 type argument is {} too many", m - n),
+        _ => format!("{m} type arguments are {} too many", m - n),
+    }
+}
+
+fn type_params(n: usize) -> String {
+    match n {
+        1 => "1 type parameter".to_owned(),
+        _ => format!("{n} type parameters"),
+    }
+}
+
+/// Attaches a note to the next diagnostic emitted through it.
+#[derive(Debug)]
+struct Noted<'b, E> {
+    emitter: &'b mut E,
+    note: Option<String>,
+}
+
+impl<S, E: Emitter<S>> Emitter<S> for Noted<'_, E> {
+    fn diagnostic(&mut self, span: S, message: impl ToString) -> impl Diagnostic<S> {
+        let diagnostic = self.emitter.diagnostic(span, message);
+        match self.note.take() {
+            Some(note) => diagnostic.note(note),
+            None => diagnostic,
+        }
+    }
+
+    fn info(&mut self, span: S, message: impl ToString) -> impl Diagnostic<S> {
+        self.emitter.info(span, message)
+    }
 }
 
 #[derive(Debug)]
@@ -461,7 +645,7 @@ pub struct Type<'a, I> {
     id: typecheck::TypeId,
 }
 
-impl<I: Clone + Importer> fmt::Display for Type<'_, I> {
+impl<'a, I: Clone + Importer<'a>> fmt::Display for Type<'a, I> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.printer.print_ty(f, self.id)
     }
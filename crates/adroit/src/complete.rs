@@ -0,0 +1,567 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    compile::{FullModule, GraphImporter, Printer},
+    fetch::{builtins, read},
+    graph::{Data, Graph, Uri},
+    lex::{lex, TokenId, TokenKind, Tokens},
+    parse::{self, Bind},
+    util::Id,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Module,
+    Def,
+    Local,
+    Field,
+    Type,
+    TypeParam,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: Kind,
+
+    /// The printed type of the name, if known.
+    pub detail: Option<String>,
+}
+
+/// What kind of thing is being written at the cursor.
+#[derive(Clone, Copy, Debug)]
+enum Context {
+    /// Inside the string of an `import`.
+    Module,
+
+    /// In the list of names after `use`, given the token of the module string.
+    Use(TokenId),
+
+    /// Where a record field name goes, when building or destructuring a record.
+    Field,
+
+    /// In a type annotation.
+    Type,
+
+    /// Where nothing can be completed, like the name of a new definition.
+    Nothing,
+
+    /// Anywhere else, where any name in scope could go.
+    Expr,
+}
+
+const TYPES: [&str; 6] = ["Float", "Float32", "Float64", "Int", "Int32", "Int64"];
+
+/// Just enough of a module to complete names in it, even if it doesn't parse.
+struct Doc<'a> {
+    source: &'a str,
+    tokens: &'a Tokens,
+}
+
+impl<'a> Doc<'a> {
+    fn kind(&self, i: usize) -> TokenKind {
+        self.tokens.get(TokenId::from_usize(i).unwrap()).kind
+    }
+
+    fn text(&self, i: usize) -> &'a str {
+        &self.source[self
+            .tokens
+            .get(TokenId::from_usize(i).unwrap())
+            .byte_range()]
+    }
+
+    /// The index of the nearest token before `i` that isn't a comment or newline.
+    fn before(&self, i: usize) -> Option<usize> {
+        (0..i).rev().find(|&j| !self.kind(j).ignore())
+    }
+
+    /// The index of the nearest token after `i` that isn't a comment or newline.
+    fn after(&self, i: usize) -> Option<usize> {
+        (i + 1..self.tokens.len()).find(|&j| !self.kind(j).ignore())
+    }
+
+    /// The index of the innermost bracket before `i` that isn't closed before `i`.
+    fn enclosing(&self, i: usize) -> Option<usize> {
+        let mut depth = 0;
+        for j in (0..i).rev() {
+            match self.kind(j) {
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => depth += 1,
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => {
+                    if depth == 0 {
+                        return Some(j);
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Whether the bracket at `open` starts the list of type parameters of a definition.
+    fn declares_types(&self, open: usize) -> bool {
+        let def = self.before(open).and_then(|k| self.before(k));
+        self.kind(open) == TokenKind::LBracket && def.map(|k| self.kind(k)) == Some(TokenKind::Def)
+    }
+
+    /// The context of a cursor placed right before token `i`.
+    fn context(&self, i: usize) -> Context {
+        let Some(prev) = self.before(i) else {
+            return Context::Nothing;
+        };
+        // the list of imported names can only contain names and commas
+        let mut j = prev;
+        if let TokenKind::Use | TokenKind::Comma = self.kind(prev) {
+            while let TokenKind::Ident | TokenKind::Comma = self.kind(j) {
+                match self.before(j) {
+                    Some(k) => j = k,
+                    None => break,
+                }
+            }
+            if let TokenKind::Use = self.kind(j) {
+                return match self.before(j) {
+                    Some(k) if self.kind(k) == TokenKind::String => {
+                        Context::Use(TokenId::from_usize(k).unwrap())
+                    }
+                    _ => Context::Nothing,
+                };
+            }
+        }
+        match self.kind(prev) {
+            TokenKind::Def | TokenKind::Index | TokenKind::Import | TokenKind::Use => {
+                return Context::Nothing;
+            }
+            TokenKind::LBrace | TokenKind::Comma => match self.enclosing(i) {
+                Some(open) if self.kind(open) == TokenKind::LBrace => return Context::Field,
+                Some(open) if self.declares_types(open) => return Context::Nothing,
+                _ => {}
+            },
+            TokenKind::LBracket if self.declares_types(prev) => return Context::Nothing,
+            _ => {}
+        }
+        // types can't span lines or contain commas, so look back for the colon that starts one
+        let mut j = prev;
+        loop {
+            match self.kind(j) {
+                TokenKind::Colon => return Context::Type,
+                TokenKind::Ident
+                | TokenKind::Number
+                | TokenKind::LParen
+                | TokenKind::RParen
+                | TokenKind::LBracket
+                | TokenKind::RBracket
+                | TokenKind::Star
+                | TokenKind::Plus
+                | TokenKind::To
+                    if j > 0 =>
+                {
+                    j -= 1
+                }
+                _ => break,
+            }
+        }
+        Context::Expr
+    }
+
+    /// The names of all record fields written in the module.
+    fn fields(&self) -> Vec<&'a str> {
+        let mut fields = vec![];
+        let mut brackets = vec![];
+        let mut prev = None;
+        for j in 0..self.tokens.len() {
+            let kind = self.kind(j);
+            match kind {
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => brackets.push(kind),
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                    brackets.pop();
+                }
+                TokenKind::Ident => {
+                    if let (Some(TokenKind::LBrace | TokenKind::Comma), Some(TokenKind::LBrace)) =
+                        (prev, brackets.last())
+                    {
+                        fields.push(self.text(j));
+                    }
+                }
+                _ => {}
+            }
+            if !kind.ignore() {
+                prev = Some(kind);
+            }
+        }
+        fields
+    }
+
+    /// The index of the token after `i` if it's a name.
+    fn ident(&self, i: usize) -> Option<usize> {
+        self.after(i).filter(|&j| self.kind(j) == TokenKind::Ident)
+    }
+
+    /// The names of all top-level definitions in the module.
+    fn defs(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.tokens.len())
+            .filter(|&j| self.kind(j) == TokenKind::Def)
+            .filter_map(|j| self.ident(j))
+    }
+
+    /// The index of the bracket that closes the one at `open`, if it's closed before `end`.
+    fn closing(&self, open: usize, end: usize) -> Option<usize> {
+        let mut depth = 0;
+        for j in open..end {
+            match self.kind(j) {
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(j);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The index of the first token from `start` to `end` that matches and isn't in brackets.
+    fn unnested(&self, start: usize, end: usize, f: impl Fn(TokenKind) -> bool) -> Option<usize> {
+        let mut depth = 0;
+        for j in start..end {
+            match self.kind(j) {
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => depth -= 1,
+                kind if depth == 0 && f(kind) => return Some(j),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Collect the names bound by the binding between tokens `start` and `end`.
+    fn binds(&self, start: usize, end: usize, names: &mut Vec<usize>) {
+        let mut depth = 0;
+        let mut ty = None; // the depth of the type annotation we're in, if any
+        for j in start..end {
+            match self.kind(j) {
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                    if ty == Some(depth) {
+                        ty = None;
+                    }
+                    depth -= 1;
+                }
+                TokenKind::Comma if ty == Some(depth) => ty = None,
+                TokenKind::Colon if ty.is_none() => ty = Some(depth),
+                // in `{x = y}` only `y` is bound, but in `{x}` it's `x`
+                TokenKind::Ident if ty.is_none() => match self.after(j) {
+                    Some(k) if k < end && self.kind(k) == TokenKind::Equal => {}
+                    _ => names.push(j),
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// The names in scope right before token `i`, with whether each is a type or a value.
+    ///
+    /// This only looks at tokens so that it works even if the module doesn't parse, which means
+    /// that it can include some local names whose scope has actually already ended.
+    fn scope(&self, i: usize) -> Vec<(usize, Level)> {
+        let mut names: Vec<_> = self.defs().map(|k| (k, Level::Def)).collect();
+        for j in (0..self.tokens.len()).filter(|&j| self.kind(j) == TokenKind::Use) {
+            let mut k = j;
+            while let Some(name) = self.ident(k) {
+                names.push((name, Level::Def));
+                match self.after(name) {
+                    Some(comma) if self.kind(comma) == TokenKind::Comma => k = comma,
+                    _ => break,
+                }
+            }
+        }
+        let Some(def) = (0..i).rev().find(|&j| self.kind(j) == TokenKind::Def) else {
+            return names;
+        };
+        let header = self
+            .unnested(def, i, |kind| {
+                matches!(kind, TokenKind::Colon | TokenKind::Equal)
+            })
+            .unwrap_or(i);
+        let mut locals = vec![];
+        let mut j = def;
+        while j < i {
+            match self.kind(j) {
+                // type parameters of the current definition
+                TokenKind::LBracket if self.declares_types(j) => {
+                    let end = self.closing(j, i).unwrap_or(i);
+                    let params = (j..end).filter(|&k| self.kind(k) == TokenKind::Ident);
+                    names.extend(params.map(|k| (k, Level::Type)));
+                    j = end;
+                }
+                TokenKind::Index => names.extend(self.ident(j).map(|k| (k, Level::Type))),
+                TokenKind::Let => {
+                    let end = self
+                        .unnested(j, i, |kind| kind == TokenKind::Equal)
+                        .unwrap_or(i);
+                    self.binds(j + 1, end, &mut locals);
+                    j = end;
+                }
+                TokenKind::Ident
+                    if self.after(j).map(|k| self.kind(k)) == Some(TokenKind::Arrow) =>
+                {
+                    locals.push(j)
+                }
+                // parameters of the current definition or of a lambda
+                TokenKind::LParen => {
+                    if let Some(close) = self.closing(j, i) {
+                        let after = self.after(close).map(|k| self.kind(k));
+                        if j < header || matches!(after, Some(TokenKind::Colon | TokenKind::Arrow))
+                        {
+                            self.binds(j + 1, close, &mut locals);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            j += 1;
+        }
+        names.extend(locals.into_iter().map(|k| (k, Level::Local)));
+        names
+    }
+}
+
+/// Where a name in scope comes from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Level {
+    Def,
+    Local,
+    Type,
+}
+
+/// The printed types of every definition exported by an analyzed module.
+pub fn exports(graph: &Graph, uri: &Uri) -> HashMap<String, String> {
+    let Data::Analyzed { syn, sem, deps, .. } = &graph.get(uri).data else {
+        return HashMap::new();
+    };
+    let full = FullModule {
+        source: &syn.src.text,
+        tokens: &syn.toks,
+        tree: &syn.tree,
+        module: Arc::clone(sem),
+    };
+    let printer = Printer::new(full, GraphImporter { graph, deps });
+    (sem.exports())
+        .map(|(name, id)| {
+            let ty = printer.ty(sem.val(sem.def(id)).ty);
+            (name.to_owned(), ty.to_string())
+        })
+        .collect()
+}
+
+/// The printed types of names declared in an analyzed module, keyed by their tokens.
+fn types(graph: &Graph, uri: &Uri) -> HashMap<TokenId, String> {
+    let Data::Analyzed { syn, sem, deps, .. } = &graph.get(uri).data else {
+        return HashMap::new();
+    };
+    let mut types = HashMap::new();
+    for (import, (uri, _)) in syn.tree.imports().iter().zip(deps.iter()) {
+        let exports = exports(graph, uri);
+        for &name in &import.names {
+            let text = &syn.src.text[syn.toks.get(name).byte_range()];
+            if let Some(ty) = exports.get(text) {
+                types.insert(name, ty.clone());
+            }
+        }
+    }
+    let full = FullModule {
+        source: &syn.src.text,
+        tokens: &syn.toks,
+        tree: &syn.tree,
+        module: Arc::clone(sem),
+    };
+    let printer = Printer::new(full, GraphImporter { graph, deps });
+    for (i, def) in syn.tree.defs().iter().enumerate() {
+        let id = parse::DefId::from_usize(i).unwrap();
+        types.insert(def.name, printer.ty(sem.val(sem.def(id)).ty).to_string());
+    }
+    for (i, param) in syn.tree.params().iter().enumerate() {
+        if let Bind::Name { name } = param.bind {
+            let id = parse::ParamId::from_usize(i).unwrap();
+            types.insert(name, printer.ty(sem.val(sem.param(id)).ty).to_string());
+        }
+    }
+    types
+}
+
+/// Everything that could be written at the given byte offset in a module.
+pub fn complete(graph: &Graph, uri: &Uri, offset: usize) -> Vec<Completion> {
+    let (source, tokens) = match &graph.get(uri).data {
+        Data::Pending | Data::Read { .. } => return vec![],
+        Data::Lexed { src, toks, err: _ } => (src.text.as_str(), toks),
+        Data::Parsed { syn } | Data::Analyzed { syn, .. } => (syn.src.text.as_str(), &syn.toks),
+    };
+    let doc = Doc { source, tokens };
+    // the token being written, or the one after the cursor if it's between tokens
+    let Some(i) = (0..tokens.len()).find(|&j| {
+        let range = tokens.get(TokenId::from_usize(j).unwrap()).byte_range();
+        let inside = match doc.kind(j) {
+            TokenKind::Ident | TokenKind::Comment => range.start < offset && offset <= range.end,
+            TokenKind::String => range.start < offset && offset < range.end,
+            _ => false,
+        };
+        inside || range.start >= offset
+    }) else {
+        return vec![];
+    };
+    let context = match doc.kind(i) {
+        TokenKind::String => match doc.before(i) {
+            Some(k) if doc.kind(k) == TokenKind::Import => Context::Module,
+            _ => Context::Nothing,
+        },
+        TokenKind::Comment => Context::Nothing,
+        _ => doc.context(i),
+    };
+    let mut completions: Vec<Completion> = match context {
+        Context::Module => (builtins())
+            .map(|name| Completion {
+                label: name.to_owned(),
+                kind: Kind::Module,
+                detail: None,
+            })
+            .collect(),
+        Context::Use(module) => {
+            let name = tokens.get(module).string(source);
+            let Ok(import) = uri.resolve(graph.stdlib(), &name) else {
+                return vec![];
+            };
+            let exports = if graph.nodes().any(|(uri, _)| *uri == import) {
+                exports(graph, &import)
+            } else {
+                HashMap::new()
+            };
+            if !exports.is_empty() {
+                (exports.into_iter())
+                    .map(|(label, ty)| Completion {
+                        label,
+                        kind: Kind::Def,
+                        detail: Some(ty),
+                    })
+                    .collect()
+            } else {
+                // the module isn't loaded yet because this one hasn't parsed since it was imported
+                let Ok(text) = read(graph.stdlib(), &import) else {
+                    return vec![];
+                };
+                let Ok(tokens) = lex(&text) else {
+                    return vec![];
+                };
+                let doc = Doc {
+                    source: &text,
+                    tokens: &tokens,
+                };
+                (doc.defs())
+                    .map(|j| Completion {
+                        label: doc.text(j).to_owned(),
+                        kind: Kind::Def,
+                        detail: None,
+                    })
+                    .collect()
+            }
+        }
+        Context::Field => {
+            let mut fields = doc.fields();
+            if let Data::Analyzed { sem, .. } = &graph.get(uri).data {
+                fields.extend(sem.fields());
+            }
+            (fields.into_iter())
+                .map(|field| Completion {
+                    label: field.to_owned(),
+                    kind: Kind::Field,
+                    detail: None,
+                })
+                .collect()
+        }
+        Context::Type => {
+            let builtins = TYPES.into_iter().map(|name| Completion {
+                label: name.to_owned(),
+                kind: Kind::Type,
+                detail: None,
+            });
+            let params = (doc.scope(i).into_iter())
+                .filter(|&(_, level)| level == Level::Type)
+                .map(|(j, _)| Completion {
+                    label: doc.text(j).to_owned(),
+                    kind: Kind::TypeParam,
+                    detail: None,
+                });
+            builtins.chain(params).collect()
+        }
+        Context::Nothing => vec![],
+        Context::Expr => {
+            let types = types(graph, uri);
+            (doc.scope(i).into_iter().rev())
+                .filter_map(|(j, level)| {
+                    let kind = match level {
+                        Level::Def => Kind::Def,
+                        Level::Local => Kind::Local,
+                        Level::Type => return None,
+                    };
+                    let detail = types.get(&TokenId::from_usize(j).unwrap()).cloned();
+                    let label = doc.text(j).to_owned();
+                    Some(Completion {
+                        label,
+                        kind,
+                        detail,
+                    })
+                })
+                .collect()
+        }
+    };
+    // later bindings shadow earlier ones, which come later in the list because of the reversal
+    let mut seen = HashSet::new();
+    completions.retain(|completion| seen.insert(completion.label.clone()));
+    completions.sort_by(|a, b| a.label.cmp(&b.label));
+    completions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(src: &str, cursor: &str) -> Vec<(String, Kind)> {
+        let uri = Uri::from_file_path("/a.adroit").unwrap();
+        let mut graph = Graph::new(Uri::from_directory_path("/std").unwrap());
+        graph.make_root(uri.clone());
+        graph.set_text(&uri, src.to_owned());
+        let offset = src.find(cursor).unwrap() + cursor.len();
+        (complete(&graph, &uri, offset).into_iter())
+            .map(|completion| (completion.label, completion.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_complete_broken() {
+        let src = "def f[T](x: T, {a = b}: Int): T =\n  let {c, d} = {c = x, d = b}\n  c +\n";
+        assert_eq!(
+            labels(src, "c +"),
+            [
+                ("b".to_owned(), Kind::Local),
+                ("c".to_owned(), Kind::Local),
+                ("d".to_owned(), Kind::Local),
+                ("f".to_owned(), Kind::Def),
+                ("x".to_owned(), Kind::Local),
+            ],
+        );
+        let fields: Vec<_> = labels(src, "{c = x, ")
+            .into_iter()
+            .map(|(l, _)| l)
+            .collect();
+        assert_eq!(fields, ["a", "c", "d"]);
+        let types: Vec<_> = labels(src, "x: ").into_iter().map(|(l, _)| l).collect();
+        assert_eq!(
+            types,
+            ["Float", "Float32", "Float64", "Int", "Int32", "Int64", "T"]
+        );
+        assert!(labels(src, "def ").is_empty());
+    }
+}
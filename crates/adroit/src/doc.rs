@@ -0,0 +1,109 @@
+use std::{fmt::Write, ops::Range, sync::Arc};
+
+use crate::{
+    compile::{FullModule, GraphImporter, Printer},
+    graph::{Data, Graph, Uri},
+    lex::{TokenId, TokenKind, Tokens},
+    parse,
+    signature::def_label,
+    symbol::{self, Symbol},
+    util::Id,
+};
+
+/// The documentation of a definition, from the lines starting with `##` right above it.
+pub fn comment(
+    source: &str,
+    tokens: &Tokens,
+    tree: &parse::Module,
+    id: parse::DefId,
+) -> Option<String> {
+    let token = |index: u32| tokens.get(TokenId { index });
+    let mut lines = vec![];
+    // the `def` keyword always comes right before the name
+    let mut index = tree.def(id).name.index - 1;
+    while index >= 2 {
+        let (newline, comment) = (token(index - 1), token(index - 2));
+        // a comment at the end of a line of code belongs to that code
+        let own_line = index == 2 || token(index - 3).kind == TokenKind::Newline;
+        if !(newline.kind == TokenKind::Newline && comment.kind == TokenKind::Comment && own_line) {
+            break;
+        }
+        let Some(line) = source[comment.byte_range()].strip_prefix("##") else {
+            break;
+        };
+        lines.push(line.strip_prefix(' ').unwrap_or(line).trim_end());
+        index -= 2;
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// Markdown describing the definition named at a byte offset in a module, along with the range of
+/// that name.
+pub fn describe(graph: &Graph, uri: &Uri, offset: usize) -> Option<(String, Range<usize>)> {
+    let (symbol, bytes) = symbol::resolve(graph, uri, |resolver| resolver.find(offset))??;
+    let Symbol::Def { src, id } = symbol else {
+        return None;
+    };
+    let (home, from) = match src {
+        None => (uri.clone(), None),
+        Some(src) => {
+            let (Data::Parsed { syn } | Data::Analyzed { syn, .. }) = &graph.get(uri).data else {
+                return None;
+            };
+            let token = syn.toks.get(syn.tree.imports()[src.to_usize()].module);
+            let home = graph.imports(uri).ok()?.swap_remove(src.to_usize());
+            (home, Some(syn.src.text[token.byte_range()].to_owned()))
+        }
+    };
+    let Data::Analyzed { syn, sem, deps, .. } = &graph.get(&home).data else {
+        return None;
+    };
+    let full = FullModule {
+        source: &syn.src.text,
+        tokens: &syn.toks,
+        tree: &syn.tree,
+        module: Arc::clone(sem),
+    };
+    let printer = Printer::new(full.clone(), GraphImporter { graph, deps });
+    let mut text = format!("```adroit\ndef {}\n```", def_label(&full, &printer, id));
+    if let Some(from) = from {
+        write!(text, "\n\nfrom `{from}`").unwrap();
+    }
+    if let Some(doc) = comment(&syn.src.text, &syn.toks, &syn.tree, id) {
+        write!(text, "\n\n---\n\n{doc}").unwrap();
+    }
+    Some((text, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lex::lex, parse::parse};
+
+    use super::*;
+
+    fn docs(src: &str) -> Vec<Option<String>> {
+        let tokens = lex(src).unwrap();
+        let tree = parse(&tokens).unwrap();
+        (0..tree.defs().len())
+            .map(|i| comment(src, &tokens, &tree, parse::DefId::from_usize(i).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_comment() {
+        let src = "## The first.\n##\n## More.\ndef f: Int = 1 ## not this\ndef g: Int = 2\n\n# nor this\ndef h: Int = 3\n## this\r\ndef k: Int = 4\n";
+        assert_eq!(
+            docs(src),
+            [
+                Some("The first.\n\nMore.".to_owned()),
+                None,
+                None,
+                Some("this".to_owned()),
+            ],
+        );
+    }
+}
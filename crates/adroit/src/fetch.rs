@@ -1,8 +1,22 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-use crate::graph::Uri;
+use crate::{graph::Uri, lex::lex, parse::parse};
 
-fn builtin(path: &Path) -> Result<&'static str, ()> {
+const BUILTINS: [(&str, &str); 3] = [
+    ("array", include_str!("modules/array.adroit")),
+    ("autodiff", include_str!("modules/autodiff.adroit")),
+    ("math", include_str!("modules/math.adroit")),
+];
+
+/// The names of all the standard library modules.
+pub fn builtins() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|&(name, _)| name)
+}
+
+pub fn builtin(path: &Path) -> Result<&'static str, ()> {
     let name = match path.to_str() {
         Some(string) => string.strip_suffix(".adroit").unwrap(),
         None => {
@@ -10,34 +24,108 @@ fn builtin(path: &Path) -> Result<&'static str, ()> {
             return Err(());
         }
     };
-    match name {
-        "array" => Ok(include_str!("modules/array.adroit")),
-        "autodiff" => Ok(include_str!("modules/autodiff.adroit")),
-        "math" => Ok(include_str!("modules/math.adroit")),
-        _ => {
+    match BUILTINS.iter().find(|&&(builtin, _)| builtin == name) {
+        Some(&(_, text)) => Ok(text),
+        None => {
             eprintln!("builtin module does not exist: {name}");
             Err(())
         }
     }
 }
 
-pub fn fetch(stdlib: &Uri, uri: &Uri) -> Result<String, String> {
+/// The text of a module, taking standard library modules from the binary instead of from disk.
+pub fn read(stdlib: &Uri, uri: &Uri) -> Result<String, String> {
     let uri_str = uri.as_str();
     let path = uri
         .to_file_path()
         .map_err(|()| format!("not a local file: {uri_str}"))?;
     let stdlib_path = stdlib.to_file_path().unwrap();
     match path.strip_prefix(&stdlib_path) {
-        Ok(relative) => {
-            let text = builtin(relative)
-                .map_err(|()| format!("not a standard library module: {}", relative.display()))?;
-            let parent = path.parent().unwrap();
-            fs::create_dir_all(parent)
-                .map_err(|err| format!("failed to make directory {}: {err}", parent.display()))?;
-            fs::write(&path, text)
-                .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
-            Ok(text.to_owned())
-        }
+        Ok(relative) => match builtin(relative) {
+            Ok(text) => Ok(text.to_owned()),
+            Err(()) => Err(format!(
+                "not a standard library module: {}",
+                relative.display()
+            )),
+        },
         Err(_) => fs::read_to_string(path).map_err(|err| format!("error reading {uri_str}: {err}")),
     }
 }
+
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    let parent = path.parent().unwrap();
+    fs::create_dir_all(parent)
+        .map_err(|err| format!("failed to make directory {}: {err}", parent.display()))?;
+    fs::write(path, text).map_err(|err| format!("failed to write {}: {err}", path.display()))
+}
+
+/// Like [`read`], but also writes standard library modules to disk, so that they can be opened.
+pub fn fetch(stdlib: &Uri, uri: &Uri) -> Result<String, String> {
+    let text = read(stdlib, uri)?;
+    let path = uri.to_file_path().unwrap();
+    if path.starts_with(stdlib.to_file_path().unwrap()) {
+        write(&path, &text)?;
+    }
+    Ok(text)
+}
+
+/// Write every standard library module to disk at once, for a long-running process that would
+/// otherwise have to write them again whenever it reads them.
+pub fn install(stdlib: &Uri) -> Result<(), String> {
+    let dir = stdlib.to_file_path().unwrap();
+    for &(name, text) in BUILTINS.iter() {
+        write(&dir.join(format!("{name}.adroit")), text)?;
+    }
+    Ok(())
+}
+
+/// The name of each module that a module's text imports, if it parses.
+fn import_names(text: &str) -> Option<Vec<String>> {
+    let tokens = lex(text).ok()?;
+    let tree = parse(&tokens).ok()?;
+    let imports = tree.imports().iter();
+    Some(
+        imports
+            .map(|import| tokens.get(import.module).string(text))
+            .collect(),
+    )
+}
+
+/// Every module under the given directories that imports the given one, along with its text, so
+/// that modules nothing else loaded can still be kept up to date.
+pub fn importers(dirs: &[PathBuf], stdlib: &Uri, module: &Uri) -> Vec<(Uri, String)> {
+    let mut found = vec![];
+    let mut stack = dirs.to_vec();
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let (path, Ok(kind)) = (entry.path(), entry.file_type()) else {
+                continue;
+            };
+            // directories like `.git` hold no modules, and links could lead in circles
+            if entry.file_name().to_string_lossy().starts_with('.') || kind.is_symlink() {
+                continue;
+            }
+            if kind.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            if path.extension() != Some("adroit".as_ref()) {
+                continue;
+            }
+            let (Ok(uri), Ok(text)) = (Uri::from_file_path(&path), fs::read_to_string(&path))
+            else {
+                continue;
+            };
+            // a module that doesn't parse can't import anything yet
+            let names = import_names(&text).unwrap_or_default();
+            if (names.iter()).any(|name| uri.resolve(stdlib, name).as_ref() == Ok(module)) {
+                found.push((uri, text));
+            }
+        }
+    }
+    found.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+    found
+}
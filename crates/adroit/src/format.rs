@@ -0,0 +1,87 @@
+use std::{iter, ops::Range};
+
+use similar::{DiffTag, TextDiff};
+
+use crate::{
+    graph::{Data, Graph, Uri},
+    lex::{TokenId, TokenKind},
+    pprint::pprint,
+    rename::Edit,
+    util::Id,
+};
+
+/// The byte offset of the start of each line, and of the end of the last one.
+fn offsets(lines: &[&str]) -> Vec<usize> {
+    let ends = lines.iter().scan(0, |end, line| {
+        *end += line.len();
+        Some(*end)
+    });
+    iter::once(0).chain(ends).collect()
+}
+
+/// The edits that turn one text into another, replacing whole lines.
+pub fn diff(old: &str, new: &str) -> Vec<Edit> {
+    let diff = TextDiff::from_lines(old, new);
+    let (before, after) = (offsets(diff.old_slices()), offsets(diff.new_slices()));
+    (diff.ops().iter())
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| {
+            let (a, b) = (op.old_range(), op.new_range());
+            Edit {
+                range: before[a.start]..before[a.end],
+                text: new[after[b.start]..after[b.end]].to_owned(),
+            }
+        })
+        .collect()
+}
+
+/// The edits that format a module, or why it can't be formatted.
+///
+/// If a byte range is given, only the edits that touch it are included.
+pub fn format(graph: &Graph, uri: &Uri, range: Option<Range<usize>>) -> Result<Vec<Edit>, String> {
+    let syn = match &graph.get(uri).data {
+        Data::Parsed { syn } | Data::Analyzed { syn, .. } => syn,
+        Data::Pending => return Err("module has not been read".to_owned()),
+        Data::Read { .. } | Data::Lexed { .. } => {
+            return Err("can't format a module with syntax errors".to_owned())
+        }
+    };
+    // the pretty printer only sees the tree, which has no comments
+    let comment = (0..syn.toks.len())
+        .any(|i| syn.toks.get(TokenId::from_usize(i).unwrap()).kind == TokenKind::Comment);
+    if comment {
+        return Err("formatting would remove comments".to_owned());
+    }
+    let mut bytes = vec![];
+    pprint(&mut bytes, &syn.src.text, &syn.toks, &syn.tree).unwrap();
+    let formatted = String::from_utf8(bytes).unwrap();
+    let edits = diff(&syn.src.text, &formatted);
+    Ok(match range {
+        Some(range) => (edits.into_iter())
+            .filter(|edit| edit.range.start <= range.end && range.start <= edit.range.end)
+            .collect(),
+        None => edits,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rename::apply;
+
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = "def f: Int =\n  1\n\ndef  g: Int = 2\n\n\ndef h: Int = 3\n";
+        let new = "def f: Int =\n  1\n\ndef g: Int = 2\n\ndef h: Int = 3\n";
+        let edits = diff(old, new);
+        assert_eq!(
+            edits,
+            [Edit {
+                range: 18..35,
+                text: "def g: Int = 2\n".to_owned(),
+            }],
+        );
+        assert_eq!(apply(old, &edits), new);
+    }
+}
@@ -1,13 +1,14 @@
 use std::{
-    collections::{hash_map::Entry, HashMap, HashSet},
-    mem::take,
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    mem::{replace, take},
     ops::Range,
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
 };
 
-use line_index::{LineCol, LineIndex};
+use itertools::Itertools;
+use line_index::{LineCol, LineIndex, WideEncoding, WideLineCol};
 use serde::{Serialize, Serializer};
 use url::Url;
 
@@ -50,12 +51,26 @@ impl Uri {
         lsp_types::Uri::from_str(self.0.as_str()).map_err(|_| ())
     }
 
-    fn resolve(&self, stdlib: &Self, name: &str) -> Result<Self, ()> {
+    /// The URI of the module imported by this one under the given name.
+    pub fn resolve(&self, stdlib: &Self, name: &str) -> Result<Self, ()> {
         let relative = name.starts_with("./") || name.starts_with("../");
         let base = if relative { self } else { stdlib };
         let url = base.0.join(&format!("{name}.adroit")).map_err(|_| ())?;
         Ok(Self::new(url))
     }
+
+    /// The name this module would import another one under, if it can import it at all.
+    pub fn import_name(&self, stdlib: &Self, other: &Self) -> Option<String> {
+        let path = match other.as_str().strip_prefix(stdlib.as_str()) {
+            Some(path) => path.to_owned(),
+            None => match self.0.make_relative(&other.0)? {
+                path if path.starts_with("../") => path,
+                path => format!("./{path}"),
+            },
+        };
+        let name = path.strip_suffix(".adroit")?.to_owned();
+        (self.resolve(stdlib, &name).ok()? == *other).then_some(name)
+    }
 }
 
 impl Serialize for Uri {
@@ -64,12 +79,29 @@ impl Serialize for Uri {
     }
 }
 
-fn linecol_to_byte(index: &LineIndex, line_col: LineCol) -> Option<usize> {
+/// Columns count UTF-8 bytes unless a wide encoding is given.
+fn linecol_to_byte(
+    index: &LineIndex,
+    wide: Option<WideEncoding>,
+    line_col: LineCol,
+) -> Option<usize> {
+    let line_col = match wide {
+        None => line_col,
+        Some(enc) => {
+            let LineCol { line, col } = line_col;
+            index.to_utf8(enc, WideLineCol { line, col })?
+        }
+    };
     Some(index.offset(line_col)?.into())
 }
 
-fn linecols_to_bytes(index: &LineIndex, start: LineCol, end: LineCol) -> Option<Range<usize>> {
-    Some(linecol_to_byte(index, start)?..linecol_to_byte(index, end)?)
+fn linecols_to_bytes(
+    index: &LineIndex,
+    wide: Option<WideEncoding>,
+    start: LineCol,
+    end: LineCol,
+) -> Option<Range<usize>> {
+    Some(linecol_to_byte(index, wide, start)?..linecol_to_byte(index, wide, end)?)
 }
 
 #[derive(Clone, Debug)]
@@ -79,7 +111,7 @@ pub struct Source {
 }
 
 impl Source {
-    fn new(text: String) -> Self {
+    pub fn new(text: String) -> Self {
         let lines = LineIndex::new(&text);
         Self { text, lines }
     }
@@ -99,8 +131,9 @@ fn unwrap_or_clone_source(arc: Arc<Syntax>) -> Source {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum Data {
+    #[default]
     Pending,
     Read {
         src: Source,
@@ -118,6 +151,9 @@ pub enum Data {
         syn: Arc<Syntax>,
         sem: Arc<typecheck::Module>,
         errs: Vec<typecheck::TypeError>,
+
+        /// The imported modules exactly as they were when this module was typechecked.
+        deps: Box<[(Uri, Arc<typecheck::Module>)]>,
     },
 }
 
@@ -136,12 +172,6 @@ impl Data {
     }
 }
 
-impl Default for Data {
-    fn default() -> Self {
-        Self::Pending
-    }
-}
-
 #[derive(Debug, Default)]
 pub struct Node {
     /// Whether or not this node is a root.
@@ -188,6 +218,33 @@ pub struct Node {
 
 pub type Analysis = (Uri, Arc<Syntax>, Box<[(Uri, Arc<typecheck::Module>)]>);
 
+/// A chain of imports leading from a module back to itself.
+#[derive(Debug)]
+pub struct Cycle {
+    /// Every module along the way, starting and ending with the same one.
+    pub uris: Vec<Uri>,
+}
+
+impl Cycle {
+    pub fn message(&self, stdlib: &Uri) -> String {
+        let first = &self.uris[0];
+        // a module can't import itself by name, but this is what it would be called if it could
+        let name = |uri: &Uri| {
+            if uri == first {
+                let file = uri.0.path_segments()?.next_back()?;
+                Some(format!("./{}", file.strip_suffix(".adroit")?))
+            } else {
+                first.import_name(stdlib, uri)
+            }
+        };
+        let names = self.uris.iter().map(|uri| match name(uri) {
+            Some(name) => format!("\"{name}\""),
+            None => uri.as_str().to_owned(),
+        });
+        format!("import cycle: {}", names.format(" -> "))
+    }
+}
+
 #[derive(Debug)]
 pub struct Graph {
     stdlib: Uri,
@@ -247,6 +304,30 @@ impl Graph {
             .collect()
     }
 
+    /// The shortest cycle that goes through one of a module's imports, if there is one.
+    pub fn cycle(&self, uri: &Uri, import: &Uri) -> Option<Cycle> {
+        let mut preds = HashMap::new();
+        let mut queue = VecDeque::from([import.clone()]);
+        while let Some(pred) = queue.pop_front() {
+            if pred == *uri {
+                let mut uris = vec![pred];
+                while let Some(pred) = preds.get(uris.last().unwrap()) {
+                    uris.push(Uri::clone(pred));
+                }
+                uris.push(uri.clone());
+                uris.reverse();
+                return Some(Cycle { uris });
+            }
+            for succ in self.imports(&pred).unwrap_or_default() {
+                if succ != *import && !preds.contains_key(&succ) {
+                    preds.insert(succ.clone(), pred.clone());
+                    queue.push_back(succ);
+                }
+            }
+        }
+        None
+    }
+
     fn make_node(&mut self, uri: Uri) -> &mut Node {
         match self.nodes.entry(uri.clone()) {
             Entry::Occupied(entry) => entry.into_mut(),
@@ -268,11 +349,57 @@ impl Graph {
         }
     }
 
+    /// Drop the types of a module and of everything that imports it, directly or transitively.
+    fn forget(&mut self, uri: &Uri) {
+        let mut seen = HashSet::new();
+        let mut stack = vec![uri.clone()];
+        while let Some(uri) = stack.pop() {
+            if !seen.insert(uri.clone()) {
+                continue;
+            }
+            let node = self.nodes.get_mut(&uri).unwrap();
+            if let Data::Analyzed { syn, .. } = &node.data {
+                node.data = Data::Parsed {
+                    syn: Arc::clone(syn),
+                };
+            }
+            stack.extend(node.dependents.iter().cloned());
+        }
+        self.analysis.retain(|(uri, _, _)| !seen.contains(uri));
+    }
+
+    /// Remove every node that no root keeps alive, directly or transitively.
+    fn collect(&mut self) {
+        let mut alive = HashSet::new();
+        let mut stack: Vec<Uri> = self.roots().map(|(uri, _)| uri.clone()).collect();
+        while let Some(uri) = stack.pop() {
+            if alive.insert(uri.clone()) {
+                stack.extend(self.get(&uri).dependencies.iter().cloned());
+            }
+        }
+        let dead: Vec<Uri> = (self.nodes.keys())
+            .filter(|uri| !alive.contains(*uri))
+            .cloned()
+            .collect();
+        for uri in &dead {
+            let node = self.nodes.remove(uri).unwrap();
+            for dep in node.dependencies {
+                if let Some(dep) = self.nodes.get_mut(&dep) {
+                    dep.dependents.remove(uri);
+                }
+            }
+        }
+        self.pending.retain(|uri| alive.contains(uri));
+        self.analysis.retain(|(uri, _, _)| alive.contains(uri));
+    }
+
     fn replace_text(&mut self, uri: &Uri, src: Source) {
-        // currently we never delete nodes, so this should always be fine; in future we should
-        // consider just returning early if there is no node with this URI, which could maybe happen
-        // if someone completes a job to read a file after that file is no longer needed
-        let node = self.nodes.get_mut(uri).unwrap();
+        // someone might finish reading a file after that file is no longer needed
+        let Some(node) = self.nodes.get_mut(uri) else {
+            return;
+        };
+        // the text might come from somewhere other than reading the file, like an editor
+        self.pending.retain(|pending| pending != uri);
         let mut kill = if node.root {
             HashSet::new()
         } else {
@@ -317,14 +444,26 @@ impl Graph {
                     .push((uri.clone(), syn, deps.into_boxed_slice()));
             }
         }
+        let collect = !kill.is_empty();
         for pred in kill {
             self.nodes.get_mut(&pred).unwrap().dependents.remove(uri);
-            // TODO: delete all nodes that roots don't depend on
         }
         let node = self.nodes.get_mut(uri).unwrap();
         node.dirty = dirty;
         let succs = node.dependents.iter().cloned().collect();
         self.propagate(uri, succs);
+        // nothing in or downstream of a cycle can be typechecked until the cycle is broken, and old
+        // types from before it formed could refer to modules that are no longer analyzed
+        let imports = self.imports(uri).unwrap_or_default();
+        if imports
+            .iter()
+            .any(|import| self.cycle(uri, import).is_some())
+        {
+            self.forget(uri);
+        }
+        if collect {
+            self.collect();
+        }
     }
 
     fn typecheck(&self, uri: &Uri) -> Result<Analysis, ()> {
@@ -357,13 +496,53 @@ impl Graph {
         self.make_node(uri.clone()).root = true;
     }
 
+    /// Read a node's text again if it's in the graph, unless it's a root, whose text comes from
+    /// somewhere other than the file.
+    pub fn reload(&mut self, uri: &Uri) {
+        if let Some(node) = self.nodes.get(uri) {
+            if !node.root && !self.pending.contains(uri) {
+                self.pending.push(uri.clone());
+            }
+        }
+    }
+
+    /// Stop keeping a node alive for its own sake, and remove whatever is no longer needed.
+    ///
+    /// If the node is still imported by something else, it goes back to pending, since its text
+    /// may have been edited without being saved.
+    pub fn remove_root(&mut self, uri: &Uri) {
+        let imports: HashSet<Uri> = match &self.get(uri).data {
+            Data::Parsed { syn } | Data::Analyzed { syn, .. } => (syn.tree.imports().iter())
+                .filter_map(|import| {
+                    let name = syn.toks.get(import.module).string(&syn.src.text);
+                    uri.resolve(&self.stdlib, &name).ok()
+                })
+                .collect(),
+            _ => HashSet::new(),
+        };
+        let node = self.nodes.get_mut(uri).unwrap();
+        node.root = false;
+        let old = replace(&mut node.dependencies, imports);
+        for dep in old
+            .difference(&node.dependencies)
+            .cloned()
+            .collect::<Vec<_>>()
+        {
+            self.nodes.get_mut(&dep).unwrap().dependents.remove(uri);
+        }
+        self.collect();
+        self.reload(uri);
+    }
+
     pub fn set_text(&mut self, uri: &Uri, text: String) {
         self.replace_text(uri, Source::new(text))
     }
 
+    /// Apply edits one after another, with columns counted in a wide encoding if one is given.
     pub fn change_text(
         &mut self,
         uri: &Uri,
+        wide: Option<WideEncoding>,
         changes: impl Iterator<Item = (LineCol, LineCol, String)>,
     ) {
         let mut src = match take(&mut self.nodes.get_mut(uri).unwrap().data) {
@@ -374,43 +553,55 @@ impl Graph {
             Data::Analyzed { syn, .. } => unwrap_or_clone_source(syn),
         };
         for (start, end, text) in changes {
-            let range = linecols_to_bytes(&src.lines, start, end).unwrap();
+            let range = linecols_to_bytes(&src.lines, wide, start, end).unwrap();
             src.text.replace_range(range, &text);
             src.lines = LineIndex::new(&src.text);
         }
         self.replace_text(uri, src)
     }
 
-    pub fn supply_semantic(
-        &mut self,
-        (uri, syn, deps): Analysis,
-        sem: Arc<typecheck::Module>,
-        errs: Vec<typecheck::TypeError>,
-    ) {
-        let node = match self.nodes.get(&uri) {
+    /// Whether the module or its imports have changed since an analysis job was made for it, so
+    /// that its result would no longer be needed.
+    pub fn outdated(&self, (uri, syn, deps): &Analysis) -> bool {
+        let node = match self.nodes.get(uri) {
             Some(node) => node,
-            None => return, // node was removed from the graph, no longer needed
+            None => return true, // node was removed from the graph, no longer needed
         };
         let current = match &node.data {
             Data::Parsed { syn, .. } => syn,
             Data::Analyzed { syn, .. } => syn,
-            _ => return, // lex or parse error so text must have changed; the result is outdated
+            _ => return true, // lex or parse error so text must have changed
         };
-        if !(node.dirty.is_empty() && Arc::ptr_eq(&syn, current)) {
-            return; // dependencies dirty or text changed, so the typechecking result is outdated
+        if !(node.dirty.is_empty() && Arc::ptr_eq(syn, current)) {
+            return true; // dependencies dirty or text changed
         }
-        if !deps
+        // dependencies changed
+        !deps
             .iter()
             .all(|(import, before)| match &self.get(import).data {
                 Data::Analyzed { sem, .. } => Arc::ptr_eq(before, sem),
                 _ => false,
             })
-        {
-            return; // dependencies changed, so the typechecking result is outdated
+    }
+
+    pub fn supply_semantic(
+        &mut self,
+        job: Analysis,
+        sem: Arc<typecheck::Module>,
+        errs: Vec<typecheck::TypeError>,
+    ) {
+        if self.outdated(&job) {
+            return;
         }
+        let (uri, syn, deps) = job;
         let node = self.nodes.get_mut(&uri).unwrap();
         let no_errs = errs.is_empty();
-        node.data = Data::Analyzed { syn, sem, errs };
+        node.data = Data::Analyzed {
+            syn,
+            sem,
+            errs,
+            deps,
+        };
         if no_errs {
             let succs: Vec<Uri> = node.dependents.iter().cloned().collect();
             for succ in succs {
@@ -437,15 +628,198 @@ mod tests {
         graph.make_root(uri.clone());
         graph.set_text(&uri, "".to_owned());
         let pos = LineCol { line: 0, col: 0 };
-        graph.change_text(&uri, vec![(pos, pos, "d".to_owned())].into_iter());
+        graph.change_text(&uri, None, vec![(pos, pos, "d".to_owned())].into_iter());
         let pos = LineCol { line: 0, col: 1 };
-        graph.change_text(&uri, vec![(pos, pos, "e".to_owned())].into_iter());
+        graph.change_text(&uri, None, vec![(pos, pos, "e".to_owned())].into_iter());
         let pos = LineCol { line: 0, col: 2 };
-        graph.change_text(&uri, vec![(pos, pos, "f".to_owned())].into_iter());
+        graph.change_text(&uri, None, vec![(pos, pos, "f".to_owned())].into_iter());
         let src = match &graph.get(&uri).data {
             Data::Lexed { src, .. } => src,
             _ => panic!(),
         };
         assert_eq!(src.text, "def");
     }
+
+    #[test]
+    fn test_wide_change() {
+        let uri = Uri::new(Url::parse("file:///dev/null").unwrap());
+        let mut graph = Graph::new(uri.clone());
+        graph.make_root(uri.clone());
+        graph.set_text(&uri, "# 😀 x\n".to_owned());
+        // the emoji is two UTF-16 code units but four UTF-8 bytes
+        let start = LineCol { line: 0, col: 5 };
+        let end = LineCol { line: 0, col: 6 };
+        let changes = vec![(start, end, "y".to_owned())];
+        graph.change_text(&uri, Some(WideEncoding::Utf16), changes.into_iter());
+        let src = match &graph.get(&uri).data {
+            Data::Read { src, .. } | Data::Lexed { src, .. } => src,
+            Data::Parsed { syn } | Data::Analyzed { syn, .. } => &syn.src,
+            Data::Pending => panic!(),
+        };
+        assert_eq!(src.text, "# 😀 y\n");
+    }
+
+    fn uri(path: &str) -> Uri {
+        Uri::from_file_path(path).unwrap()
+    }
+
+    /// Read every pending module from a list of paths and texts.
+    fn read(graph: &mut Graph, files: &[(&str, &str)]) {
+        loop {
+            let pending = graph.pending();
+            if pending.is_empty() {
+                break;
+            }
+            for uri in pending {
+                let (_, text) = files
+                    .iter()
+                    .find(|&&(path, _)| uri == self::uri(path))
+                    .unwrap();
+                graph.set_text(&uri, text.to_string());
+            }
+        }
+    }
+
+    /// Typecheck modules until there's nothing left that can be typechecked.
+    fn analyze(graph: &mut Graph) {
+        loop {
+            let analysis = graph.analysis();
+            if analysis.is_empty() {
+                break;
+            }
+            for job in analysis {
+                let (_, syn, deps) = &job;
+                let (module, errs) = typecheck::typecheck(
+                    &syn.src.text,
+                    &syn.toks,
+                    &syn.tree,
+                    deps.iter().map(|(_, dep)| dep.as_ref()).collect(),
+                );
+                graph.supply_semantic(job, Arc::new(module), errs);
+            }
+        }
+    }
+
+    fn paths(graph: &Graph) -> Vec<String> {
+        let mut paths: Vec<String> = graph
+            .nodes()
+            .map(|(uri, _)| uri.as_str().to_owned())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_remove_root() {
+        let files = [
+            ("/a.adroit", "import \"./b\" use f\n"),
+            ("/b.adroit", "import \"./c\" use g\n"),
+            ("/c.adroit", ""),
+            ("/d.adroit", "import \"./b\" use f\n"),
+        ];
+        let mut graph = Graph::new(uri("/std/"));
+        graph.make_root(uri("/a.adroit"));
+        graph.make_root(uri("/d.adroit"));
+        read(&mut graph, &files);
+        assert_eq!(paths(&graph).len(), 4);
+        graph.remove_root(&uri("/a.adroit"));
+        assert_eq!(
+            paths(&graph),
+            ["file:///b.adroit", "file:///c.adroit", "file:///d.adroit"],
+        );
+        assert!(graph.get(&uri("/b.adroit")).dependents == HashSet::from([uri("/d.adroit")]));
+        graph.remove_root(&uri("/d.adroit"));
+        assert!(paths(&graph).is_empty());
+        assert!(graph.pending().is_empty());
+    }
+
+    #[test]
+    fn test_reload_imported_root() {
+        let files = [
+            ("/a.adroit", "import \"./b\" use f\n"),
+            ("/b.adroit", "def f: Int = 1\n"),
+        ];
+        let mut graph = Graph::new(uri("/std/"));
+        graph.make_root(uri("/a.adroit"));
+        graph.make_root(uri("/b.adroit"));
+        read(&mut graph, &files);
+        graph.set_text(&uri("/b.adroit"), "def f: Int = 2\n".to_owned());
+        graph.remove_root(&uri("/b.adroit"));
+        // still imported, so it has to be read again to drop the unsaved edit
+        assert_eq!(graph.pending(), [uri("/b.adroit")]);
+        assert!(!graph.get(&uri("/b.adroit")).root);
+    }
+
+    #[test]
+    fn test_collect_unimported() {
+        let files = [
+            ("/a.adroit", "import \"./b\" use f\n"),
+            ("/b.adroit", "import \"./c\" use g\n"),
+            ("/c.adroit", ""),
+        ];
+        let mut graph = Graph::new(uri("/std/"));
+        graph.make_root(uri("/a.adroit"));
+        read(&mut graph, &files);
+        // a module that isn't a root forgets its old imports when its text changes
+        graph.set_text(&uri("/b.adroit"), "".to_owned());
+        assert_eq!(paths(&graph), ["file:///a.adroit", "file:///b.adroit"]);
+        // but a root keeps them until it's closed
+        graph.set_text(&uri("/a.adroit"), "".to_owned());
+        assert_eq!(paths(&graph), ["file:///a.adroit", "file:///b.adroit"]);
+        graph.remove_root(&uri("/a.adroit"));
+        assert!(paths(&graph).is_empty());
+    }
+
+    #[test]
+    fn test_reload() {
+        let files = [
+            ("/a.adroit", "import \"./b\" use f\n"),
+            ("/b.adroit", "def f: Int = 1\n"),
+        ];
+        let mut graph = Graph::new(uri("/std/"));
+        graph.make_root(uri("/a.adroit"));
+        read(&mut graph, &files);
+        graph.reload(&uri("/a.adroit"));
+        graph.reload(&uri("/b.adroit"));
+        graph.reload(&uri("/c.adroit"));
+        assert_eq!(graph.pending(), [uri("/b.adroit")]);
+        graph.set_text(&uri("/b.adroit"), "def f: Int = 2\n".to_owned());
+        assert!(graph.get(&uri("/a.adroit")).dirty == HashSet::from([uri("/b.adroit")]));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let files = [
+            ("/a.adroit", "import \"./b\" use f\ndef g: Int = f\n"),
+            ("/b.adroit", "def f: Int = 1\n"),
+        ];
+        let mut graph = Graph::new(uri("/std/"));
+        graph.make_root(uri("/a.adroit"));
+        read(&mut graph, &files);
+        analyze(&mut graph);
+        assert!(matches!(
+            graph.get(&uri("/a.adroit")).data,
+            Data::Analyzed { .. }
+        ));
+        let text = "import \"./a\" use g\ndef f: Int = 1\n".to_owned();
+        graph.set_text(&uri("/b.adroit"), text);
+        analyze(&mut graph);
+        // the old types of the first module would refer to the second, which is now stuck
+        assert!(matches!(
+            graph.get(&uri("/a.adroit")).data,
+            Data::Parsed { .. }
+        ));
+        let cycle = graph.cycle(&uri("/a.adroit"), &uri("/b.adroit")).unwrap();
+        assert_eq!(
+            cycle.message(graph.stdlib()),
+            "import cycle: \"./a\" -> \"./b\" -> \"./a\"",
+        );
+        graph.set_text(&uri("/b.adroit"), "def f: Int = 1\n".to_owned());
+        analyze(&mut graph);
+        assert!(graph.cycle(&uri("/a.adroit"), &uri("/b.adroit")).is_none());
+        assert!(matches!(
+            graph.get(&uri("/a.adroit")).data,
+            Data::Analyzed { .. }
+        ));
+    }
 }
@@ -0,0 +1,215 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::Range,
+};
+
+use crate::{
+    graph::{Data, Graph, Uri},
+    lex::TokenId,
+    parse::{self, Bind, Expr},
+    typecheck::{self, Src},
+    util::Id,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Module,
+    Def,
+    Param,
+    Local,
+    Field,
+    Type,
+    TypeParam,
+}
+
+/// A name in a module, classified by what it refers to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Highlight {
+    pub range: Range<usize>,
+    pub kind: Kind,
+
+    /// Whether this is where the name is declared, rather than a use of it.
+    pub declaration: bool,
+
+    /// Whether the name refers to something from the standard library.
+    pub stdlib: bool,
+}
+
+/// Add a parameter and all the parameters nested inside it.
+fn binds(tree: &parse::Module, id: parse::ParamId, params: &mut HashSet<parse::ParamId>) {
+    params.insert(id);
+    match tree.param(id).bind {
+        Bind::Paren { inner } => binds(tree, inner, params),
+        Bind::Pair { fst, snd } => {
+            binds(tree, fst, params);
+            binds(tree, snd, params);
+        }
+        Bind::Record { field, rest, .. } => {
+            binds(tree, field, params);
+            binds(tree, rest, params);
+        }
+        Bind::Unit { .. } | Bind::Name { .. } | Bind::End { .. } => {}
+    }
+}
+
+/// The names in an analyzed module, sorted and without overlaps.
+pub fn highlights(graph: &Graph, uri: &Uri) -> Option<Vec<Highlight>> {
+    let Data::Analyzed { syn, sem, .. } = &graph.get(uri).data else {
+        return None;
+    };
+    let uris = graph.imports(uri).ok()?;
+    let (tree, module) = (&syn.tree, sem.as_ref());
+    let stdlib = |src: typecheck::ImportId| {
+        uris[src.to_usize()]
+            .as_str()
+            .starts_with(graph.stdlib().as_str())
+    };
+    // a shorthand like `{x}` is both a field and a variable, so fields only fill in the gaps
+    let mut names = BTreeMap::new();
+    let mut fields = vec![];
+    let mut add = |token: TokenId, kind, declaration, stdlib| {
+        let range = syn.toks.get(token).byte_range();
+        names.entry(range.start).or_insert(Highlight {
+            range,
+            kind,
+            declaration,
+            stdlib,
+        });
+    };
+    for (i, import) in tree.imports().iter().enumerate() {
+        let src = typecheck::ImportId::from_usize(i).unwrap();
+        add(import.module, Kind::Module, false, stdlib(src));
+        for &name in &import.names {
+            add(name, Kind::Def, false, stdlib(src));
+        }
+    }
+    let mut locals = vec![];
+    for def in tree.defs() {
+        add(def.name, Kind::Def, true, false);
+        for &ty in &def.types {
+            add(ty, Kind::TypeParam, true, false);
+        }
+    }
+    for (i, expr) in tree.exprs().iter().enumerate() {
+        let id = parse::ExprId::from_usize(i).unwrap();
+        match *expr {
+            Expr::Name { name } => match module.origin(id) {
+                Src::Def { .. } => add(name, Kind::Def, false, false),
+                Src::Import { src, .. } => add(name, Kind::Def, false, stdlib(src)),
+                Src::Param { id } => locals.push((name, id, false)),
+                Src::Expr { .. } | Src::Inst { .. } => {}
+            },
+            Expr::Record { name, .. } => fields.push(name),
+            Expr::Index { name, .. } => add(name, Kind::TypeParam, true, false),
+            _ => {}
+        }
+    }
+    let mut lets = HashSet::new();
+    for expr in tree.exprs() {
+        if let Expr::Let { param, .. } = *expr {
+            binds(tree, param, &mut lets);
+        }
+    }
+    for (i, param) in tree.params().iter().enumerate() {
+        let id = parse::ParamId::from_usize(i).unwrap();
+        match param.bind {
+            Bind::Name { name } => locals.push((name, id, true)),
+            Bind::Record { name, .. } => fields.push(name),
+            _ => {}
+        }
+    }
+    for (name, id, declaration) in locals {
+        let kind = if lets.contains(&id) {
+            Kind::Local
+        } else {
+            Kind::Param
+        };
+        add(name, kind, declaration, false);
+    }
+    for (i, ty) in tree.types().iter().enumerate() {
+        if let parse::Type::Name { name } = *ty {
+            let id = parse::TypeId::from_usize(i).unwrap();
+            match module.ty(module.parsed_ty(id)) {
+                typecheck::Type::Var { .. } => add(name, Kind::TypeParam, false, false),
+                _ => add(name, Kind::Type, false, true),
+            }
+        }
+    }
+    for name in fields {
+        add(name, Kind::Field, false, false);
+    }
+    Some(names.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::typecheck::typecheck;
+
+    use super::*;
+
+    fn classify(lib: &str, main: &str) -> Vec<(String, Kind, bool, bool)> {
+        let mut graph = Graph::new(Uri::from_directory_path("/std").unwrap());
+        let uri = Uri::from_file_path("/main.adroit").unwrap();
+        for (path, text) in [("/lib.adroit", lib), ("/main.adroit", main)] {
+            let uri = Uri::from_file_path(path).unwrap();
+            graph.make_root(uri.clone());
+            graph.set_text(&uri, text.to_owned());
+        }
+        loop {
+            let analysis = graph.analysis();
+            if analysis.is_empty() {
+                break;
+            }
+            for job in analysis {
+                let (_, syn, deps) = &job;
+                let imports = deps.iter().map(|(_, dep)| dep.as_ref()).collect();
+                let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, imports);
+                graph.supply_semantic(job, Arc::new(module), errs);
+            }
+        }
+        (highlights(&graph, &uri).unwrap().into_iter())
+            .map(|hl| {
+                (
+                    main[hl.range].to_owned(),
+                    hl.kind,
+                    hl.declaration,
+                    hl.stdlib,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_highlights() {
+        let lib = "def sq(x: Float): Float = x * x\n";
+        let main = "import \"./lib\" use sq\n\ndef f[T](t: T, x: Float): Float =\n  let {y} = {y = sq(x)}\n  y\n";
+        let names: Vec<_> = (classify(lib, main).into_iter())
+            .map(|(text, kind, declaration, stdlib)| {
+                assert!(stdlib == (text == "Float"));
+                (text, kind, declaration)
+            })
+            .collect();
+        let expected = [
+            ("\"./lib\"", Kind::Module, false),
+            ("sq", Kind::Def, false),
+            ("f", Kind::Def, true),
+            ("T", Kind::TypeParam, true),
+            ("t", Kind::Param, true),
+            ("T", Kind::TypeParam, false),
+            ("x", Kind::Param, true),
+            ("Float", Kind::Type, false),
+            ("Float", Kind::Type, false),
+            // a shorthand binding shows as the local it declares, not the field it takes apart
+            ("y", Kind::Local, true),
+            ("y", Kind::Field, false),
+            ("sq", Kind::Def, false),
+            ("x", Kind::Param, false),
+            ("y", Kind::Local, false),
+        ];
+        let expected =
+            expected.map(|(text, kind, declaration)| (text.to_owned(), kind, declaration));
+        assert_eq!(names, expected);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    compile::{FullModule, GraphImporter, Printer},
+    graph::{Data, Graph, Uri},
+    parse::{self, Bind, Expr},
+    range::{bind_range, param_range, ty_range},
+    typecheck::{self, Src},
+    util::Id,
+};
+
+/// What a hint is about, so that each kind can be turned off on its own.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    /// The type of a parameter or `let` binding.
+    Param,
+
+    /// The result type of a lambda.
+    Result,
+
+    /// The type arguments of a generic function.
+    TypeArgs,
+}
+
+/// Text to show at a byte offset in a module, without it being part of the source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hint {
+    pub offset: usize,
+    pub label: String,
+    pub kind: Kind,
+}
+
+/// Add a parameter and all the parameters nested inside it that have no type annotation.
+pub fn unannotated(tree: &parse::Module, id: parse::ParamId, params: &mut Vec<parse::ParamId>) {
+    let param = tree.param(id);
+    if param.ty.is_some() {
+        return;
+    }
+    match param.bind {
+        Bind::Name { name: _ } => params.push(id),
+        Bind::Paren { inner } => unannotated(tree, inner, params),
+        Bind::Pair { fst, snd } => {
+            unannotated(tree, fst, params);
+            unannotated(tree, snd, params);
+        }
+        Bind::Record { field, rest, .. } => {
+            unannotated(tree, field, params);
+            unannotated(tree, rest, params);
+        }
+        Bind::Unit { .. } | Bind::End { .. } => {}
+    }
+}
+
+/// The inferred types of unannotated parameters and lambda results, and the inferred type
+/// arguments of generic functions, in an analyzed module.
+pub fn hints(graph: &Graph, uri: &Uri) -> Option<Vec<Hint>> {
+    let Data::Analyzed { syn, sem, deps, .. } = &graph.get(uri).data else {
+        return None;
+    };
+    let (tokens, tree, module) = (&syn.toks, &syn.tree, sem.as_ref());
+    let full = FullModule {
+        source: &syn.src.text,
+        tokens,
+        tree,
+        module: Arc::clone(sem),
+    };
+    let printer = Printer::new(full, GraphImporter { graph, deps });
+    // a type that's still unknown here would only be noise
+    let print = |ty: typecheck::TypeId| Some(printer.ty(ty).to_string()).filter(|ty| ty != "_");
+    let mut hints = vec![];
+    let mut params = vec![];
+    let mut results = vec![];
+    for def in tree.defs() {
+        for &param in &def.params {
+            unannotated(tree, param, &mut params);
+        }
+    }
+    // explicit type arguments are `Inst` expressions around a name
+    let mut insts = HashMap::new();
+    for (i, expr) in tree.exprs().iter().enumerate() {
+        if let Expr::Inst { val, ty } = *expr {
+            insts.insert(val, (parse::ExprId::from_usize(i).unwrap(), ty));
+        }
+    }
+    for (i, expr) in tree.exprs().iter().enumerate() {
+        let id = parse::ExprId::from_usize(i).unwrap();
+        match *expr {
+            Expr::Let { param, .. } => unannotated(tree, param, &mut params),
+            Expr::Lambda { param, ty, .. } => {
+                unannotated(tree, param, &mut params);
+                if let (None, typecheck::Type::Func { dom: _, cod }) =
+                    (ty, module.ty(module.val(module.expr(id)).ty))
+                {
+                    if let Some(ty) = print(cod) {
+                        let offset = param_range(tokens, tree, param).unwrap().end;
+                        let label = format!(": {ty}");
+                        results.push(Hint {
+                            offset,
+                            label,
+                            kind: Kind::Result,
+                        });
+                    }
+                }
+            }
+            Expr::Name { name } => {
+                // type arguments are applied innermost first, each with its own `Inst` value
+                let mut args = vec![];
+                let mut v = module.expr(id);
+                while let Src::Inst { val, ty } = module.val(v).src {
+                    args.push(ty);
+                    v = val;
+                }
+                args.reverse();
+                let (mut explicit, mut last) = (0, None);
+                let mut head = id;
+                while let Some(&(inst, ty)) = insts.get(&head) {
+                    (explicit, last, head) = (explicit + 1, Some(ty), inst);
+                }
+                let inferred: Vec<String> = (args.iter().skip(explicit))
+                    .map(|&ty| printer.ty(ty).to_string())
+                    .collect();
+                if inferred.is_empty() {
+                    continue;
+                }
+                let (offset, label) = match last {
+                    Some(ty) => (
+                        ty_range(tokens, tree, ty).unwrap().end,
+                        format!(", {}", inferred.join(", ")),
+                    ),
+                    None => (
+                        tokens.get(name).byte_range().end,
+                        format!("[{}]", inferred.join(", ")),
+                    ),
+                };
+                hints.push(Hint {
+                    offset,
+                    label,
+                    kind: Kind::TypeArgs,
+                });
+            }
+            _ => {}
+        }
+    }
+    for param in params {
+        if let Some(ty) = print(module.val(module.param(param)).ty) {
+            let offset = bind_range(tokens, tree, param).unwrap().end;
+            let label = format!(": {ty}");
+            hints.push(Hint {
+                offset,
+                label,
+                kind: Kind::Param,
+            });
+        }
+    }
+    // a lambda's result type goes after its parameter's type, since both can be at the same offset
+    hints.extend(results);
+    hints.sort_by_key(|hint| hint.offset);
+    Some(hints)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typecheck::typecheck;
+
+    use super::*;
+
+    fn labels(src: &str) -> Vec<(String, String, Kind)> {
+        let uri = Uri::from_file_path("/a.adroit").unwrap();
+        let mut graph = Graph::new(Uri::from_directory_path("/std").unwrap());
+        graph.make_root(uri.clone());
+        graph.set_text(&uri, src.to_owned());
+        for job in graph.analysis() {
+            let (_, syn, _) = &job;
+            let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, vec![]);
+            graph.supply_semantic(job, Arc::new(module), errs);
+        }
+        // each hint along with the source just before it
+        (hints(&graph, &uri).unwrap().into_iter())
+            .map(|hint| {
+                let before = src[..hint.offset].rsplit(' ').next().unwrap().to_owned();
+                (before, hint.label, hint.kind)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hints() {
+        let src = "def k[A, B](x: A, y: B): A = x\n\ndef f(a: Float, n: Int): Float =\n  let g = x => k(x, n + 1)\n  let (b, c) = (k[Float](g(a), a), 2)\n  b\n";
+        let expected = [
+            ("g", ": Float -> Float", Kind::Param),
+            ("x", ": Float", Kind::Param),
+            // after the parameter's own hint, even though they're at the same offset
+            ("x", ": Float", Kind::Result),
+            ("k", "[Float, Int]", Kind::TypeArgs),
+            ("(b", ": Float", Kind::Param),
+            ("c", ": Int", Kind::Param),
+            // only the type arguments that weren't given
+            ("(k[Float", ", Float", Kind::TypeArgs),
+        ];
+        let expected =
+            expected.map(|(before, label, kind)| (before.to_owned(), label.to_owned(), kind));
+        assert_eq!(labels(src), expected);
+    }
+}
@@ -64,6 +64,9 @@ pub enum TokenKind {
     #[regex(r#""[^"]*""#)]
     String,
 
+    #[regex(r"\?\w*")]
+    Hole,
+
     #[token("(")]
     LParen,
 
@@ -158,6 +161,7 @@ impl fmt::Display for TokenKind {
             Self::Ident => write!(f, "identifier"),
             Self::Number => write!(f, "number"),
             Self::String => write!(f, "string"),
+            Self::Hole => write!(f, "hole"),
             Self::LParen => write!(f, "`(`"),
             Self::RParen => write!(f, "`)`"),
             Self::LBracket => write!(f, "`[`"),
@@ -1,36 +1,104 @@
-use std::{collections::HashMap, mem::take, ops::Range, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    mem::take,
+    num::NonZeroUsize,
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
-use crossbeam_channel::Sender;
-use itertools::Itertools;
-use line_index::{LineCol, LineIndex};
-use lsp_server::{Connection, Message, RequestId, ResponseError};
+use crossbeam_channel::{after, never, select, unbounded, Receiver, Sender};
+use line_index::{LineCol, LineIndex, WideEncoding, WideLineCol};
+use lsp_server::{Connection, ErrorCode, Message, RequestId, ResponseError};
 use lsp_types::{
     notification::{
-        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
-        Notification, PublishDiagnostics, ShowMessage,
+        Cancel, DidChangeConfiguration, DidChangeTextDocument, DidChangeWatchedFiles,
+        DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument, Notification,
+        PublishDiagnostics, ShowMessage,
     },
-    request::{HoverRequest, Request},
-    Diagnostic, DiagnosticRelatedInformation, DidChangeTextDocumentParams,
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams, Hover,
-    HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind,
-    MessageType, Position, PublishDiagnosticsParams, ServerCapabilities, ShowMessageParams,
-    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    request::{
+        CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+        CodeActionRequest, CodeLensRefresh, CodeLensRequest, Completion, DocumentDiagnosticRequest,
+        DocumentHighlightRequest, DocumentLinkRequest, DocumentSymbolRequest, FoldingRangeRequest,
+        Formatting, GotoDefinition, HoverRequest, InlayHintRefreshRequest, InlayHintRequest,
+        PrepareRenameRequest, RangeFormatting, References, RegisterCapability, Rename, Request,
+        SemanticTokensFullDeltaRequest, SemanticTokensFullRequest, SignatureHelpRequest,
+        WorkspaceConfiguration, WorkspaceDiagnosticRefresh, WorkspaceDiagnosticRequest,
+        WorkspaceSymbolRequest,
+    },
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    CallHierarchyServerCapability, CancelParams, ClientCapabilities, CodeAction, CodeActionKind,
+    CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, CodeLens, CodeLensOptions,
+    CodeLensParams, Command, CompletionItem, CompletionItemKind, CompletionOptions,
+    CompletionParams, CompletionResponse, ConfigurationItem, ConfigurationParams, Diagnostic,
+    DiagnosticOptions, DiagnosticRelatedInformation, DiagnosticServerCapabilities,
+    DiagnosticSeverity, DidChangeConfigurationParams, DidChangeTextDocumentParams,
+    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportResult,
+    DocumentFormattingParams, DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams,
+    DocumentLink, DocumentLinkOptions, DocumentLinkParams, DocumentRangeFormattingParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, FileSystemWatcher, FoldingRange,
+    FoldingRangeKind, FoldingRangeParams, FoldingRangeProviderCapability,
+    FullDocumentDiagnosticReport, GlobPattern, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverContents, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
+    InlayHint, InlayHintKind, InlayHintLabel, InlayHintParams, Location, MarkupContent, MarkupKind,
+    MessageType, NumberOrString, OneOf, ParameterInformation, ParameterLabel, Position,
+    PositionEncodingKind, PrepareRenameResponse, PublishDiagnosticsParams, ReferenceParams,
+    Registration, RegistrationParams, RelatedFullDocumentDiagnosticReport, RenameOptions,
+    RenameParams, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
+    SemanticTokensDelta, SemanticTokensDeltaParams, SemanticTokensEdit,
+    SemanticTokensFullDeltaResult, SemanticTokensFullOptions, SemanticTokensLegend,
+    SemanticTokensOptions, SemanticTokensParams, SemanticTokensResult,
+    SemanticTokensServerCapabilities, ServerCapabilities, ShowMessageParams, SignatureHelp,
+    SignatureHelpOptions, SignatureHelpParams, SignatureInformation, SymbolKind,
+    TextDocumentIdentifier, TextDocumentPositionParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, WorkspaceDiagnosticParams, WorkspaceDiagnosticReport,
+    WorkspaceDiagnosticReportResult, WorkspaceDocumentDiagnosticReport, WorkspaceEdit,
+    WorkspaceFullDocumentDiagnosticReport, WorkspaceSymbol, WorkspaceSymbolParams,
+    WorkspaceSymbolResponse,
 };
+use serde::Deserialize;
 use serde_json::Value;
 
 use crate::{
+    action::actions,
+    call,
     compile::{FullModule, GraphImporter, Printer},
-    fetch::fetch,
-    graph::{Data, Graph, Node, Uri},
-    parse::ParseError,
-    range,
-    typecheck::typecheck,
-    util::{self, Emitter},
+    complete::{self, complete},
+    doc::describe,
+    fetch::{importers, install, read},
+    format::format,
+    graph::{Analysis, Data, Graph, Node, Uri},
+    highlight::{self, highlights, Highlight},
+    hint::{self, hints},
+    outline::{self, folds, items, workspace, Item},
+    parse::{self, ParseError},
+    range, rename, signature,
+    symbol::{self, Resolver, Symbol},
+    typecheck::{self, typecheck, TypeError},
+    util::{self, Emitter, Id},
 };
 
 type ResponseResult<T> = Result<T, ResponseError>;
 
+fn request_failed(message: String) -> ResponseError {
+    ResponseError {
+        code: ErrorCode::RequestFailed as i32,
+        message,
+        data: None,
+    }
+}
+
 fn notify<N: Notification>(sender: &Sender<Message>, params: N::Params) -> anyhow::Result<()> {
     sender.send(Message::Notification(lsp_server::Notification {
         method: N::METHOD.to_owned(),
@@ -39,6 +107,39 @@ fn notify<N: Notification>(sender: &Sender<Message>, params: N::Params) -> anyho
     Ok(())
 }
 
+/// The directories of the client's workspace, if it has any on disk.
+fn folders(params: &InitializeParams) -> Vec<PathBuf> {
+    #[allow(deprecated)] // older clients only send the root
+    let uris = match (&params.workspace_folders, &params.root_uri) {
+        (Some(folders), _) => folders.iter().map(|folder| &folder.uri).collect(),
+        (None, Some(root)) => vec![root],
+        (None, None) => vec![],
+    };
+    (uris.into_iter())
+        .filter_map(|uri| Uri::from_lsp_uri(uri).ok()?.to_file_path().ok())
+        .collect()
+}
+
+/// The encoding the client counts columns in, out of the ones it supports: UTF-8 if it can, so
+/// that no conversion is needed, or otherwise the UTF-16 that every client must support.
+fn position_encoding(client: &ClientCapabilities) -> Option<WideEncoding> {
+    let encodings =
+        (client.general.as_ref()).and_then(|general| general.position_encodings.as_ref());
+    match encodings {
+        Some(encodings) if encodings.contains(&PositionEncodingKind::UTF8) => None,
+        _ => Some(WideEncoding::Utf16),
+    }
+}
+
+/// A module's line index, along with how the client counts columns in it.
+#[derive(Clone, Copy, Debug)]
+struct Lines<'a> {
+    index: &'a LineIndex,
+
+    /// Columns are UTF-8 bytes if this is `None`.
+    wide: Option<WideEncoding>,
+}
+
 fn lsp_to_linecol(pos: Position) -> LineCol {
     let line = pos.line;
     let col = pos.character;
@@ -49,39 +150,181 @@ fn lsp_to_linecols(range: lsp_types::Range) -> (LineCol, LineCol) {
     (lsp_to_linecol(range.start), lsp_to_linecol(range.end))
 }
 
-fn byte_to_lsp(index: &LineIndex, offset: usize) -> Position {
-    let LineCol { line, col } = index.line_col(offset.try_into().unwrap());
-    Position::new(line, col)
+fn byte_to_lsp(lines: Lines, offset: usize) -> Position {
+    let line_col = lines.index.line_col(offset.try_into().unwrap());
+    match lines.wide {
+        None => Position::new(line_col.line, line_col.col),
+        Some(enc) => {
+            let WideLineCol { line, col } = lines.index.to_wide(enc, line_col).unwrap();
+            Position::new(line, col)
+        }
+    }
 }
 
-fn bytes_to_lsp(index: &LineIndex, range: Range<usize>) -> lsp_types::Range {
-    let start = byte_to_lsp(index, range.start);
-    let end = byte_to_lsp(index, range.end);
+fn bytes_to_lsp(lines: Lines, range: Range<usize>) -> lsp_types::Range {
+    let start = byte_to_lsp(lines, range.start);
+    let end = byte_to_lsp(lines, range.end);
     lsp_types::Range { start, end }
 }
 
-fn lsp_to_byte(index: &LineIndex, pos: Position) -> Option<usize> {
-    Some(index.offset(lsp_to_linecol(pos))?.into())
+fn lsp_to_byte(lines: Lines, pos: Position) -> Option<usize> {
+    let line_col = match lines.wide {
+        None => lsp_to_linecol(pos),
+        Some(enc) => {
+            let (line, col) = (pos.line, pos.character);
+            lines.index.to_utf8(enc, WideLineCol { line, col })?
+        }
+    };
+    Some(lines.index.offset(line_col)?.into())
+}
+
+const TOKEN_TYPES: [SemanticTokenType; 7] = [
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::TYPE_PARAMETER,
+];
+
+const TOKEN_MODIFIERS: [SemanticTokenModifier; 2] = [
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+/// Encode highlights as semantic tokens, each relative to the one before it.
+fn semantic_tokens(lines: Lines, highlights: Vec<Highlight>) -> Vec<SemanticToken> {
+    let mut prev = Position::new(0, 0);
+    (highlights.into_iter())
+        .map(|highlight| {
+            // names never span lines, so their length is just the difference in columns
+            let lsp_types::Range { start, end } = bytes_to_lsp(lines, highlight.range);
+            let delta_line = start.line - prev.line;
+            let delta_start = match delta_line {
+                0 => start.character - prev.character,
+                _ => start.character,
+            };
+            prev = start;
+            let token_type = match highlight.kind {
+                highlight::Kind::Module => 0,
+                highlight::Kind::Def => 1,
+                highlight::Kind::Param => 2,
+                highlight::Kind::Local => 3,
+                highlight::Kind::Field => 4,
+                highlight::Kind::Type => 5,
+                highlight::Kind::TypeParam => 6,
+            };
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: end.character - start.character,
+                token_type,
+                token_modifiers_bitset: u32::from(highlight.declaration)
+                    | u32::from(highlight.stdlib) << 1,
+            }
+        })
+        .collect()
+}
+
+/// The edits that turn one encoding of semantic tokens into another, counted in integers rather than
+/// in tokens: a single edit replacing everything between their common prefix and suffix.
+fn semantic_tokens_edits(
+    prev: &[SemanticToken],
+    data: &[SemanticToken],
+) -> Vec<SemanticTokensEdit> {
+    let start = (prev.iter().zip(data)).take_while(|(a, b)| a == b).count();
+    let end = (prev[start..].iter().rev().zip(data[start..].iter().rev()))
+        .take_while(|(a, b)| a == b)
+        .count();
+    match (prev.len() - end - start, &data[start..data.len() - end]) {
+        (0, []) => vec![],
+        (deleted, inserted) => vec![SemanticTokensEdit {
+            start: (5 * start).try_into().unwrap(),
+            delete_count: (5 * deleted).try_into().unwrap(),
+            data: Some(inserted.to_vec()),
+        }],
+    }
+}
+
+fn symbol_kind(kind: outline::Kind) -> SymbolKind {
+    match kind {
+        outline::Kind::Module => SymbolKind::MODULE,
+        outline::Kind::Def => SymbolKind::FUNCTION,
+    }
+}
+
+fn document_symbol(lines: Lines, item: Item) -> DocumentSymbol {
+    let children = (item.children.into_iter())
+        .map(|child| document_symbol(lines, child))
+        .collect();
+    #[allow(deprecated)] // the `deprecated` field is, but we have to set it anyway
+    DocumentSymbol {
+        name: item.name,
+        detail: item.detail,
+        kind: symbol_kind(item.kind),
+        tags: None,
+        deprecated: None,
+        range: bytes_to_lsp(lines, item.range),
+        selection_range: bytes_to_lsp(lines, item.selection),
+        children: Some(children),
+    }
+}
+
+/// A module and its imports, for turning spans into LSP locations.
+#[derive(Debug)]
+struct Files<'a> {
+    files: HashMap<String, (lsp_types::Uri, Lines<'a>)>,
+}
+
+impl<'a> Files<'a> {
+    fn new(graph: &'a Graph, uri: &Uri, wide: Option<WideEncoding>) -> Self {
+        let mut files = HashMap::new();
+        let imports = graph.imports(uri).unwrap_or_default();
+        for dep in std::iter::once(uri).chain(&imports) {
+            if let (Data::Parsed { syn } | Data::Analyzed { syn, .. }, Ok(lsp_uri)) =
+                (&graph.get(dep).data, dep.to_lsp_uri())
+            {
+                let index = &syn.src.lines;
+                files.insert(dep.as_str().to_owned(), (lsp_uri, Lines { index, wide }));
+            }
+        }
+        Self { files }
+    }
+
+    fn range(&self, path: &str, range: Range<usize>) -> lsp_types::Range {
+        let (_, lines) = self.files[path];
+        bytes_to_lsp(lines, range)
+    }
+
+    fn location(&self, (path, range): (&str, Range<usize>)) -> Location {
+        let (uri, _) = &self.files[path];
+        Location {
+            uri: uri.clone(),
+            range: self.range(path, range),
+        }
+    }
 }
 
 #[derive(Debug)]
 struct LspEmitter<'a> {
-    uri: lsp_types::Uri,
     path: &'a str,
-    index: &'a LineIndex,
+    files: Files<'a>,
     diags: Vec<Diagnostic>,
 }
 
-impl<'a> Emitter<(&'a str, Range<usize>)> for LspEmitter<'a> {
-    fn diagnostic(
+impl<'a> LspEmitter<'a> {
+    fn report(
         &mut self,
-        (path, range): (&'a str, Range<usize>),
+        severity: Option<DiagnosticSeverity>,
+        span: (&'a str, Range<usize>),
         message: impl ToString,
-    ) -> impl util::Diagnostic<(&'a str, Range<usize>)> {
-        assert_eq!(path, self.path);
-        let range = bytes_to_lsp(self.index, range);
+    ) -> LspDiagnostic<'a, '_> {
+        assert_eq!(span.0, self.path);
+        let range = self.files.location(span).range;
         LspDiagnostic {
             emitter: self,
+            severity,
             range,
             message: message.to_string(),
             related: vec![],
@@ -89,30 +332,51 @@ impl<'a> Emitter<(&'a str, Range<usize>)> for LspEmitter<'a> {
     }
 }
 
+impl<'a> Emitter<(&'a str, Range<usize>)> for LspEmitter<'a> {
+    fn diagnostic(
+        &mut self,
+        span: (&'a str, Range<usize>),
+        message: impl ToString,
+    ) -> impl util::Diagnostic<(&'a str, Range<usize>)> {
+        self.report(None, span, message)
+    }
+
+    fn info(
+        &mut self,
+        span: (&'a str, Range<usize>),
+        message: impl ToString,
+    ) -> impl util::Diagnostic<(&'a str, Range<usize>)> {
+        self.report(Some(DiagnosticSeverity::INFORMATION), span, message)
+    }
+}
+
 #[derive(Debug)]
 struct LspDiagnostic<'a, 'b> {
     emitter: &'b mut LspEmitter<'a>,
+    severity: Option<DiagnosticSeverity>,
     range: lsp_types::Range,
     message: String,
     related: Vec<DiagnosticRelatedInformation>,
 }
 
 impl<'a> util::Diagnostic<(&'a str, Range<usize>)> for LspDiagnostic<'a, '_> {
-    fn related(mut self, (path, range): (&'a str, Range<usize>), message: impl ToString) -> Self {
-        assert_eq!(path, self.emitter.path);
+    fn related(mut self, span: (&'a str, Range<usize>), message: impl ToString) -> Self {
         self.related.push(DiagnosticRelatedInformation {
-            location: Location {
-                uri: self.emitter.uri.clone(),
-                range: bytes_to_lsp(self.emitter.index, range),
-            },
+            location: self.emitter.files.location(span),
             message: message.to_string(),
         });
         self
     }
 
+    fn note(mut self, message: impl ToString) -> Self {
+        write!(self.message, "\n\n{}", message.to_string()).unwrap();
+        self
+    }
+
     fn finish(self) {
         self.emitter.diags.push(Diagnostic {
             range: self.range,
+            severity: self.severity,
             message: self.message,
             related_information: Some(self.related),
             ..Default::default()
@@ -120,17 +384,171 @@ impl<'a> util::Diagnostic<(&'a str, Range<usize>)> for LspDiagnostic<'a, '_> {
     }
 }
 
+/// How long to wait after the last change before publishing diagnostics.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// An analysis job, along with whether it has been canceled because its result would be outdated.
+type Job = (Analysis, Arc<AtomicBool>);
+
+/// A finished analysis job, ready to be supplied to the graph.
+type Typechecked = (Job, Arc<typecheck::Module>, Vec<TypeError>);
+
+/// Typecheck modules on a thread of its own until there are no more jobs or no one to tell.
+fn work(jobs: Receiver<Job>, done: Sender<Typechecked>) {
+    for job in jobs {
+        let ((_, syn, deps), canceled) = &job;
+        if canceled.load(Ordering::Relaxed) {
+            continue;
+        }
+        let (module, errs) = typecheck(
+            &syn.src.text,
+            &syn.toks,
+            &syn.tree,
+            deps.iter().map(|(_, dep)| dep.as_ref()).collect(),
+        );
+        if done.send((job, Arc::new(module), errs)).is_err() {
+            break;
+        }
+    }
+}
+
+/// The document a request or notification is about, if any.
+fn document(params: &Value) -> Option<lsp_types::Uri> {
+    let doc = params.get("textDocument")?.clone();
+    Some(
+        serde_json::from_value::<TextDocumentIdentifier>(doc)
+            .ok()?
+            .uri,
+    )
+}
+
+/// The command that code lenses run to show references, which the client has to provide.
+const SHOW_REFERENCES: &str = "adroit.showReferences";
+
+/// The section of the client's settings that applies to us.
+const SECTION: &str = "adroit";
+
+/// Settings that the client can change while the server is running.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Config {
+    /// Where to keep standard library modules, instead of the default directory.
+    stdlib: Option<PathBuf>,
+
+    warnings: Warnings,
+    inlay_hints: InlayHints,
+}
+
+/// Which kinds of warnings to show.
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Warnings {
+    /// Imports of modules that have errors of their own.
+    imports: bool,
+}
+
+impl Default for Warnings {
+    fn default() -> Self {
+        Self { imports: true }
+    }
+}
+
+/// Which kinds of inlay hints to show.
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct InlayHints {
+    parameter_types: bool,
+    result_types: bool,
+    type_arguments: bool,
+}
+
+impl Default for InlayHints {
+    fn default() -> Self {
+        Self {
+            parameter_types: true,
+            result_types: true,
+            type_arguments: true,
+        }
+    }
+}
+
+impl InlayHints {
+    fn show(&self, kind: hint::Kind) -> bool {
+        match kind {
+            hint::Kind::Param => self.parameter_types,
+            hint::Kind::Result => self.result_types,
+            hint::Kind::TypeArgs => self.type_arguments,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct State {
     sender: Sender<Message>,
     graph: Graph,
+
+    /// Where to send modules to be typechecked in the background.
+    jobs: Sender<Job>,
+
+    /// The jobs sent that are still being typechecked or waiting to be, and not canceled.
+    running: Vec<Job>,
+
+    /// When to publish diagnostics, if nothing else changes before then.
+    publish: Option<Instant>,
+
+    /// Modules other than open documents whose diagnostics were last published nonempty.
+    shown: HashSet<Uri>,
+
+    /// How the client counts columns in positions, as for [`Lines`].
+    encoding: Option<WideEncoding>,
+
+    /// What the client can do, to know what we can ask of it.
+    client: ClientCapabilities,
+
+    /// The directories of the client's workspace, to look in for modules that aren't open.
+    folders: Vec<PathBuf>,
+
+    /// Where to keep standard library modules unless the settings say otherwise.
+    stdlib: Uri,
+
+    config: Config,
+
+    /// The semantic tokens last sent for each document, so that later requests can send a delta.
+    tokens: RefCell<HashMap<Uri, (String, Vec<SemanticToken>)>>,
+    result_id: Cell<u64>,
+
+    /// The ID of the next request we send to the client.
+    request_id: Cell<i32>,
+
+    /// Our latest request for settings, since an answer to any earlier one is already outdated.
+    config_request: Option<RequestId>,
 }
 
 impl State {
-    fn new(stdlib: Uri, sender: Sender<Message>) -> Self {
+    fn new(
+        stdlib: Uri,
+        sender: Sender<Message>,
+        jobs: Sender<Job>,
+        encoding: Option<WideEncoding>,
+        client: ClientCapabilities,
+        folders: Vec<PathBuf>,
+    ) -> Self {
         Self {
             sender,
-            graph: Graph::new(stdlib),
+            graph: Graph::new(stdlib.clone()),
+            jobs,
+            running: vec![],
+            publish: None,
+            shown: HashSet::new(),
+            encoding,
+            client,
+            folders,
+            stdlib,
+            config: Config::default(),
+            tokens: RefCell::new(HashMap::new()),
+            result_id: Cell::new(0),
+            request_id: Cell::new(0),
+            config_request: None,
         }
     }
 
@@ -138,6 +556,44 @@ impl State {
         notify::<N>(&self.sender, params)
     }
 
+    /// Send a request to the client, returning its ID so that we can recognize the response.
+    fn request<R: Request>(&self, params: R::Params) -> anyhow::Result<RequestId> {
+        let id = self.request_id.get();
+        self.request_id.set(id + 1);
+        let id = RequestId::from(id);
+        self.sender.send(Message::Request(lsp_server::Request {
+            id: id.clone(),
+            method: R::METHOD.to_owned(),
+            params: serde_json::to_value(params)?,
+        }))?;
+        Ok(id)
+    }
+
+    /// Ask the client to tell us when modules change on disk, if it lets us ask.
+    fn watch_files(&self) -> anyhow::Result<()> {
+        let dynamic = (self.client.workspace.as_ref())
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched| watched.dynamic_registration);
+        if dynamic != Some(true) {
+            return Ok(());
+        }
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.adroit".to_owned()),
+                kind: None,
+            }],
+        };
+        let params = RegistrationParams {
+            registrations: vec![Registration {
+                id: DidChangeWatchedFiles::METHOD.to_owned(),
+                method: DidChangeWatchedFiles::METHOD.to_owned(),
+                register_options: Some(serde_json::to_value(options)?),
+            }],
+        };
+        self.request::<RegisterCapability>(params)?;
+        Ok(())
+    }
+
     fn exhaust(&mut self) {
         loop {
             let pending = self.graph.pending();
@@ -145,35 +601,78 @@ impl State {
                 break;
             }
             for uri in pending {
-                if let Ok(text) = fetch(self.graph.stdlib(), &uri) {
+                if let Ok(text) = read(self.graph.stdlib(), &uri) {
                     self.graph.set_text(&uri, text);
                 }
             }
         }
-        loop {
-            let analysis = self.graph.analysis();
-            if analysis.is_empty() {
-                break;
+        // a job whose module has changed since would only have its result thrown away
+        self.running.retain(|(job, canceled)| {
+            let outdated = self.graph.outdated(job);
+            canceled.store(outdated, Ordering::Relaxed);
+            !outdated
+        });
+        for job in self.graph.analysis() {
+            if self.graph.outdated(&job) {
+                continue;
             }
-            for job in analysis {
-                let (_, syn, deps) = &job;
-                let (module, errs) = typecheck(
-                    &syn.src.text,
-                    &syn.toks,
-                    &syn.tree,
-                    deps.iter().map(|(_, dep)| dep.as_ref()).collect(),
-                );
-                let sem = Arc::new(module);
-                self.graph.supply_semantic(job, sem, errs);
+            let job = (job, Arc::new(AtomicBool::new(false)));
+            self.running.push(job.clone());
+            // the workers only stop once this sender is dropped
+            self.jobs.send(job).unwrap();
+        }
+        self.publish = Some(Instant::now() + DEBOUNCE);
+    }
+
+    fn supply(&mut self, ((job, canceled), sem, errs): Typechecked) {
+        let Some(i) = (self.running.iter()).position(|(_, other)| Arc::ptr_eq(other, &canceled))
+        else {
+            return; // canceled after the worker had already started on it
+        };
+        self.running.swap_remove(i);
+        self.graph.supply_semantic(job, sem, errs);
+        self.exhaust();
+    }
+
+    /// Whether a module or anything it imports, directly or not, still has to be typechecked.
+    fn outdated(&self, uri: &Uri) -> bool {
+        let mut stack = vec![uri.clone()];
+        let mut seen = HashSet::new();
+        while let Some(uri) = stack.pop() {
+            if !seen.insert(uri.clone()) {
+                continue;
             }
+            let node = self.graph.get(&uri);
+            let running = (self.running.iter()).any(|((other, _, _), _)| *other == uri);
+            if running || !node.dirty.is_empty() || !matches!(node.data, Data::Analyzed { .. }) {
+                return true;
+            }
+            stack.extend(self.graph.imports(&uri).unwrap_or_default());
+        }
+        false
+    }
+
+    /// Whether a request is about a document whose types are still being worked out.
+    fn waiting(&self, req: &lsp_server::Request) -> bool {
+        // these depend on every module, not just the one they're about
+        if let CodeLensRequest::METHOD | WorkspaceDiagnosticRequest::METHOD = req.method.as_str() {
+            return !self.running.is_empty();
         }
+        let Some(uri) = document(&req.params).and_then(|uri| Uri::from_lsp_uri(&uri).ok()) else {
+            return false;
+        };
+        if !self.graph.roots().any(|(root, _)| *root == uri) {
+            return false;
+        }
+        // a module whose imports have errors stays outdated, but then nothing is running for it
+        !self.running.is_empty() && self.outdated(&uri)
     }
 
-    fn diagnose(&self, uri: &Uri, lsp_uri: lsp_types::Uri, node: &Node) -> Vec<Diagnostic> {
+    fn diagnose(&self, uri: &Uri, node: &Node) -> Vec<Diagnostic> {
         match &node.data {
             Data::Pending => unreachable!(),
             Data::Read { src, err } => {
-                let range = bytes_to_lsp(&src.lines, err.byte_range());
+                let range = bytes_to_lsp(self.encode(&src.lines), err.byte_range());
                 let message = err.message().to_owned();
                 vec![Diagnostic::new_simple(range, message)]
             }
@@ -181,14 +680,44 @@ impl State {
                 let id = match *err {
                     ParseError::Expected { id, kinds: _ } => id,
                 };
-                let range = bytes_to_lsp(&src.lines, toks.get(id).byte_range());
+                let range = bytes_to_lsp(self.encode(&src.lines), toks.get(id).byte_range());
                 let message = err.message();
                 vec![Diagnostic::new_simple(range, message)]
             }
-            Data::Parsed { syn: _ } => vec![],
-            Data::Analyzed { syn, sem, errs } => {
+            // analysis is only stuck once every import is either analyzed or broken
+            Data::Parsed { syn } => (syn.tree.imports().iter())
+                .filter_map(|import| {
+                    let name = syn.toks.get(import.module).string(&syn.src.text);
+                    let dep = uri.resolve(self.graph.stdlib(), &name).ok()?;
+                    let (severity, message) = if let Some(cycle) = self.graph.cycle(uri, &dep) {
+                        (None, cycle.message(self.graph.stdlib()))
+                    } else {
+                        match &self.graph.get(&dep).data {
+                            Data::Pending => (None, "imported module could not be read".to_owned()),
+                            Data::Analyzed { errs, .. } if errs.is_empty() => return None,
+                            // the errors themselves show up in the other module, so this is only a reminder
+                            _ if !self.config.warnings.imports => return None,
+                            _ => (
+                                Some(DiagnosticSeverity::WARNING),
+                                "imported module has errors".to_owned(),
+                            ),
+                        }
+                    };
+                    let bytes = syn.toks.get(import.module).byte_range();
+                    let range = bytes_to_lsp(self.encode(&syn.src.lines), bytes);
+                    Some(Diagnostic {
+                        severity,
+                        ..Diagnostic::new_simple(range, message)
+                    })
+                })
+                .collect(),
+            Data::Analyzed {
+                syn,
+                sem,
+                errs,
+                deps,
+            } => {
                 let uri_str = uri.as_str();
-                let uris = self.graph.imports(uri).unwrap();
                 let full = FullModule {
                     source: &syn.src.text,
                     tokens: &syn.toks,
@@ -197,57 +726,208 @@ impl State {
                 };
                 let importer = GraphImporter {
                     graph: &self.graph,
-                    uris: &uris,
+                    deps,
                 };
                 let printer = Printer::new(full, importer);
                 let mut emitter = LspEmitter {
-                    uri: lsp_uri,
                     path: uri_str,
-                    index: &syn.src.lines,
+                    files: Files::new(&self.graph, uri, self.encoding),
                     diags: vec![],
                 };
                 for &err in errs {
                     printer.emit_type_error(&mut emitter, uri_str, err);
                 }
+                printer.emit_holes(&mut emitter, uri_str);
                 emitter.diags
             }
         }
     }
 
-    fn diagnose_all(&self) -> anyhow::Result<()> {
-        for (uri, node) in self.graph.roots() {
-            let lsp_uri = uri
-                .to_lsp_uri()
-                .map_err(|()| anyhow!("not a valid LSP URI: {}", uri.as_str()))?;
-            let diagnostics = self.diagnose(uri, lsp_uri.clone(), node);
-            let version = None;
-            self.notify::<PublishDiagnostics>(PublishDiagnosticsParams {
-                uri: lsp_uri,
-                diagnostics,
-                version,
-            })?;
+    fn publish(&self, uri: &Uri, diagnostics: Vec<Diagnostic>) -> anyhow::Result<()> {
+        let lsp_uri = uri
+            .to_lsp_uri()
+            .map_err(|()| anyhow!("not a valid LSP URI: {}", uri.as_str()))?;
+        let version = None;
+        self.notify::<PublishDiagnostics>(PublishDiagnosticsParams {
+            uri: lsp_uri,
+            diagnostics,
+            version,
+        })
+    }
+
+    /// Diagnostics for every open document and every module they import, and whether each is open.
+    fn diagnose_imported(&self) -> Vec<(Uri, bool, Vec<Diagnostic>)> {
+        let mut modules = vec![];
+        // roots keep alive modules they no longer import, but those are no one's problem
+        let mut stack: Vec<Uri> = self.graph.roots().map(|(uri, _)| uri.clone()).collect();
+        let mut seen = HashSet::new();
+        while let Some(uri) = stack.pop() {
+            if !seen.insert(uri.clone()) {
+                continue;
+            }
+            let node = self.graph.get(&uri);
+            if let Data::Pending = node.data {
+                continue; // there's no text to show diagnostics in
+            }
+            stack.extend(self.graph.imports(&uri).unwrap_or_default());
+            let diagnostics = self.diagnose(&uri, node);
+            modules.push((uri, node.root, diagnostics));
+        }
+        modules
+    }
+
+    /// Publish diagnostics for every open document, and for every module they import that has some.
+    fn diagnose_all(&mut self) -> anyhow::Result<()> {
+        if self.pull() {
+            // the client asks for diagnostics itself, so it only needs to know to ask again
+            let refresh = (self.client.workspace.as_ref())
+                .and_then(|workspace| workspace.diagnostic.as_ref())
+                .and_then(|diagnostic| diagnostic.refresh_support);
+            if refresh == Some(true) {
+                self.request::<WorkspaceDiagnosticRefresh>(())?;
+            }
+            return Ok(());
+        }
+        let mut before = take(&mut self.shown);
+        for (uri, root, diagnostics) in self.diagnose_imported() {
+            let cleared = before.remove(&uri);
+            if !root {
+                if diagnostics.is_empty() {
+                    if !cleared {
+                        continue;
+                    }
+                } else {
+                    self.shown.insert(uri.clone());
+                }
+            }
+            self.publish(&uri, diagnostics)?;
+        }
+        // whatever is left is no longer imported, but might still have diagnostics showing
+        for uri in before {
+            self.publish(&uri, vec![])?;
         }
         Ok(())
     }
 
+    /// Let the client know that reference counts in code lenses might have changed.
+    fn refresh_lenses(&self) -> anyhow::Result<()> {
+        let refresh = (self.client.workspace.as_ref())
+            .and_then(|workspace| workspace.code_lens.as_ref())
+            .and_then(|code_lens| code_lens.refresh_support);
+        if refresh == Some(true) {
+            self.request::<CodeLensRefresh>(())?;
+        }
+        Ok(())
+    }
+
+    /// Whether the client asks for diagnostics itself instead of waiting for us to publish them.
+    fn pull(&self) -> bool {
+        (self.client.text_document.as_ref()).is_some_and(|doc| doc.diagnostic.is_some())
+    }
+
+    /// Ask the client for our settings, if it lets us ask.
+    fn fetch_config(&mut self) -> anyhow::Result<()> {
+        let configuration =
+            (self.client.workspace.as_ref()).and_then(|workspace| workspace.configuration);
+        if configuration != Some(true) {
+            return Ok(());
+        }
+        let items = vec![ConfigurationItem {
+            scope_uri: None,
+            section: Some(SECTION.to_owned()),
+        }];
+        let id = self.request::<WorkspaceConfiguration>(ConfigurationParams { items })?;
+        self.config_request = Some(id);
+        Ok(())
+    }
+
+    /// Switch to new settings, starting over with a new graph if the standard library moved.
+    fn configure(&mut self, settings: Value) -> anyhow::Result<()> {
+        // clients with nothing set for us can send null rather than an empty object
+        let config = match serde_json::from_value::<Option<Config>>(settings) {
+            Ok(config) => config.unwrap_or_default(),
+            Err(err) => return self.warn(format!("invalid settings: {err}")),
+        };
+        let stdlib = match &config.stdlib {
+            None => self.stdlib.clone(),
+            Some(dir) => match Uri::from_directory_path(dir) {
+                Ok(uri) => uri,
+                Err(()) => {
+                    let dir = dir.display();
+                    return self.warn(format!("standard library must be an absolute path: {dir}"));
+                }
+            },
+        };
+        if stdlib != *self.graph.stdlib() {
+            if let Err(err) = install(&stdlib) {
+                self.warn(err)?;
+            }
+            let roots: Vec<(Uri, String)> = (self.graph.roots())
+                .filter_map(|(uri, node)| {
+                    let text = match &node.data {
+                        Data::Pending => return None,
+                        Data::Read { src, .. } | Data::Lexed { src, .. } => &src.text,
+                        Data::Parsed { syn } | Data::Analyzed { syn, .. } => &syn.src.text,
+                    };
+                    Some((uri.clone(), text.clone()))
+                })
+                .collect();
+            self.graph = Graph::new(stdlib);
+            for (uri, text) in roots {
+                self.graph.make_root(uri.clone());
+                self.graph.set_text(&uri, text);
+            }
+            self.tokens.get_mut().clear();
+        }
+        self.config = config;
+        let refresh = (self.client.workspace.as_ref())
+            .and_then(|workspace| workspace.inlay_hint.as_ref())
+            .and_then(|inlay_hint| inlay_hint.refresh_support);
+        if refresh == Some(true) {
+            self.request::<InlayHintRefreshRequest>(())?;
+        }
+        // even if nothing needs analyzing again, the diagnostics might need to change
+        self.exhaust();
+        Ok(())
+    }
+
+    fn warn(&self, message: String) -> anyhow::Result<()> {
+        self.notify::<ShowMessage>(ShowMessageParams {
+            typ: MessageType::WARNING,
+            message,
+        })
+    }
+
     fn hover_success(&self, doc_pos: TextDocumentPositionParams) -> Option<Hover> {
         let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).ok()?;
         let (syn, sem) = match &self.graph.get(&uri).data {
             Data::Parsed { syn } => (syn, None),
-            Data::Analyzed { syn, sem, errs: _ } => (syn, Some(sem)),
+            Data::Analyzed { syn, sem, deps, .. } => (syn, Some((sem, deps))),
             _ => return None,
         };
-        let index = &syn.src.lines;
+        let index = self.encode(&syn.src.lines);
         let offset = lsp_to_byte(index, doc_pos.position)?;
+        if let Some((value, bytes)) = describe(&self.graph, &uri, offset) {
+            return Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                }),
+                range: Some(bytes_to_lsp(index, bytes)),
+            });
+        }
         let (node, bytes) = range::find(&syn.toks, &syn.tree, offset)?;
         let ty = match (sem, node) {
-            (Some(sem), range::Node::Type(id)) => Some((sem, sem.parsed_ty(id))),
-            (Some(sem), range::Node::Param(id)) => Some((sem, sem.val(sem.param(id)).ty)),
-            (Some(sem), range::Node::Expr(id)) => Some((sem, sem.val(sem.expr(id)).ty)),
+            (Some((sem, deps)), range::Node::Type(id)) => Some((sem, deps, sem.parsed_ty(id))),
+            (Some((sem, deps)), range::Node::Param(id)) => {
+                Some((sem, deps, sem.val(sem.param(id)).ty))
+            }
+            (Some((sem, deps)), range::Node::Expr(id)) => {
+                Some((sem, deps, sem.val(sem.expr(id)).ty))
+            }
             _ => None,
         }
-        .map(|(sem, id)| {
-            let uris = self.graph.imports(&uri).unwrap();
+        .map(|(sem, deps, id)| {
             let full = FullModule {
                 source: &syn.src.text,
                 tokens: &syn.toks,
@@ -256,7 +936,7 @@ impl State {
             };
             let importer = GraphImporter {
                 graph: &self.graph,
-                uris: &uris,
+                deps,
             };
             let printer = Printer::new(full, importer);
             format!("{}", printer.ty(id))
@@ -274,15 +954,500 @@ impl State {
         })
     }
 
+    /// Resolve names in an analyzed module, along with a way to turn spans into locations.
+    fn resolve<T>(
+        &self,
+        uri: &Uri,
+        f: impl for<'b> FnOnce(&Resolver<'b, GraphImporter<'b>>, &Files<'b>, Lines) -> T,
+    ) -> Option<T> {
+        let index = match &self.graph.get(uri).data {
+            Data::Analyzed { syn, .. } => self.encode(&syn.src.lines),
+            _ => return None,
+        };
+        let files = Files::new(&self.graph, uri, self.encoding);
+        symbol::resolve(&self.graph, uri, |resolver| f(resolver, &files, index))
+    }
+
+    fn definition_success(&self, doc_pos: TextDocumentPositionParams) -> Option<Location> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).ok()?;
+        self.resolve(&uri, |resolver, files, index| {
+            let offset = lsp_to_byte(index, doc_pos.position)?;
+            let (symbol, _) = resolver.find(offset)?;
+            Some(files.location(resolver.declaration(symbol)?))
+        })?
+    }
+
+    fn references_success(
+        &self,
+        doc_pos: TextDocumentPositionParams,
+        include_declaration: bool,
+    ) -> Option<Vec<Location>> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).ok()?;
+        let (symbol, mut locations) = self.resolve(&uri, |resolver, files, index| {
+            let offset = lsp_to_byte(index, doc_pos.position)?;
+            let (symbol, _) = resolver.find(offset)?;
+            let mut locations = vec![];
+            if let (true, Some(span)) = (include_declaration, resolver.declaration(symbol)) {
+                locations.push(files.location(span));
+            }
+            Some((symbol, locations))
+        })??;
+        let uses = symbol::references(&self.graph, &uri, symbol).into_iter();
+        locations.extend(uses.filter_map(|(module, range)| self.location(&module, range)));
+        Some(locations)
+    }
+
+    fn highlight_success(
+        &self,
+        doc_pos: TextDocumentPositionParams,
+    ) -> Option<Vec<DocumentHighlight>> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).ok()?;
+        self.resolve(&uri, |resolver, _, index| {
+            let offset = lsp_to_byte(index, doc_pos.position)?;
+            let (symbol, _) = resolver.find(offset)?;
+            let mut highlights = vec![];
+            if let Some((path, range)) = resolver.declaration(symbol) {
+                if path == uri.as_str() {
+                    highlights.push(DocumentHighlight {
+                        range: bytes_to_lsp(index, range),
+                        kind: Some(DocumentHighlightKind::WRITE),
+                    });
+                }
+            }
+            highlights.extend(
+                resolver
+                    .uses(symbol)
+                    .into_iter()
+                    .map(|range| DocumentHighlight {
+                        range: bytes_to_lsp(index, range),
+                        kind: Some(DocumentHighlightKind::READ),
+                    }),
+            );
+            Some(highlights)
+        })?
+    }
+
+    fn encode<'a>(&self, index: &'a LineIndex) -> Lines<'a> {
+        Lines {
+            index,
+            wide: self.encoding,
+        }
+    }
+
+    fn lines(&self, uri: &Uri) -> Option<Lines<'_>> {
+        match &self.graph.get(uri).data {
+            Data::Pending => None,
+            Data::Read { src, .. } | Data::Lexed { src, .. } => Some(self.encode(&src.lines)),
+            Data::Parsed { syn } | Data::Analyzed { syn, .. } => Some(self.encode(&syn.src.lines)),
+        }
+    }
+
+    fn location(&self, uri: &Uri, range: Range<usize>) -> Option<Location> {
+        Some(Location {
+            uri: uri.to_lsp_uri().ok()?,
+            range: bytes_to_lsp(self.lines(uri)?, range),
+        })
+    }
+
+    fn prepare_rename_success(
+        &self,
+        doc_pos: TextDocumentPositionParams,
+    ) -> Result<lsp_types::Range, String> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).map_err(|()| "invalid URI")?;
+        let index = self.lines(&uri).ok_or("unknown document")?;
+        let offset = lsp_to_byte(index, doc_pos.position).ok_or("invalid position")?;
+        let range = rename::prepare_rename(&self.graph, &uri, offset)?;
+        Ok(bytes_to_lsp(index, range))
+    }
+
+    /// A separate graph with the open documents and some other modules as roots, analyzed right
+    /// away instead of by the workers.
+    fn load(&self, modules: Vec<(Uri, String)>) -> Graph {
+        let mut graph = Graph::new(self.graph.stdlib().clone());
+        for (uri, node) in self.graph.roots() {
+            let text = match &node.data {
+                Data::Pending => continue,
+                Data::Read { src, .. } | Data::Lexed { src, .. } => &src.text,
+                Data::Parsed { syn } | Data::Analyzed { syn, .. } => &syn.src.text,
+            };
+            graph.make_root(uri.clone());
+            graph.set_text(uri, text.clone());
+        }
+        for (uri, text) in modules {
+            graph.make_root(uri.clone());
+            graph.set_text(&uri, text);
+        }
+        loop {
+            for uri in graph.pending() {
+                if let Ok(text) = read(graph.stdlib(), &uri) {
+                    graph.set_text(&uri, text);
+                }
+            }
+            let analysis = graph.analysis();
+            if analysis.is_empty() {
+                break;
+            }
+            for job in analysis {
+                let (_, syn, deps) = &job;
+                let imports = deps.iter().map(|(_, dep)| dep.as_ref()).collect();
+                let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, imports);
+                graph.supply_semantic(job, Arc::new(module), errs);
+            }
+        }
+        graph
+    }
+
+    #[allow(clippy::mutable_key_type)] // `lsp_types::Uri` only caches its parse internally
+    fn rename_success(
+        &self,
+        doc_pos: TextDocumentPositionParams,
+        new_name: &str,
+    ) -> Result<WorkspaceEdit, String> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).map_err(|()| "invalid URI")?;
+        let index = self.lines(&uri).ok_or("unknown document")?;
+        let offset = lsp_to_byte(index, doc_pos.position).ok_or("invalid position")?;
+        // modules that aren't open and aren't imported by any that are aren't in the graph
+        let mut unloaded = vec![];
+        if let Some((home, _)) = call::def_at(&self.graph, &uri, offset) {
+            for (importer, text) in importers(&self.folders, self.graph.stdlib(), &home) {
+                if self.graph.nodes().all(|(other, _)| *other != importer) {
+                    unloaded.push((importer, text));
+                }
+            }
+        }
+        let loaded;
+        let graph = if unloaded.is_empty() {
+            &self.graph
+        } else {
+            loaded = self.load(unloaded);
+            &loaded
+        };
+        let mut changes = HashMap::new();
+        for (module, edits) in rename::rename(graph, &uri, offset, new_name)? {
+            let Data::Analyzed { syn, .. } = &graph.get(&module).data else {
+                unreachable!();
+            };
+            let index = self.encode(&syn.src.lines);
+            let lsp_uri = module.to_lsp_uri().map_err(|()| "invalid URI")?;
+            let edits = (edits.into_iter())
+                .map(|edit| TextEdit::new(bytes_to_lsp(index, edit.range), edit.text))
+                .collect();
+            changes.insert(lsp_uri, edits);
+        }
+        Ok(WorkspaceEdit::new(changes))
+    }
+
+    fn formatting_success(
+        &self,
+        uri: &lsp_types::Uri,
+        range: Option<lsp_types::Range>,
+    ) -> Result<Vec<TextEdit>, String> {
+        let uri = Uri::from_lsp_uri(uri).map_err(|()| "invalid URI")?;
+        let index = self.lines(&uri).ok_or("unknown document")?;
+        let range = match range {
+            Some(range) => {
+                let start = lsp_to_byte(index, range.start).ok_or("invalid position")?;
+                let end = lsp_to_byte(index, range.end).unwrap_or(usize::MAX);
+                Some(start..end)
+            }
+            None => None,
+        };
+        let edits = (format(&self.graph, &uri, range)?.into_iter())
+            .map(|edit| TextEdit::new(bytes_to_lsp(index, edit.range), edit.text));
+        Ok(edits.collect())
+    }
+
+    fn code_action_success(&self, params: CodeActionParams) -> Option<Vec<CodeActionOrCommand>> {
+        let lsp_uri = params.text_document.uri;
+        let uri = Uri::from_lsp_uri(&lsp_uri).ok()?;
+        let index = self.lines(&uri)?;
+        let start = lsp_to_byte(index, params.range.start)?;
+        let end = lsp_to_byte(index, params.range.end).unwrap_or(usize::MAX);
+        let actions = (actions(&self.graph, &uri, start..end).into_iter())
+            .map(|action| {
+                let edits = (action.edits.into_iter())
+                    .map(|edit| TextEdit::new(bytes_to_lsp(index, edit.range), edit.text))
+                    .collect();
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: action.title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(
+                        lsp_uri.clone(),
+                        edits,
+                    )]))),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        Some(actions)
+    }
+
+    fn completion_success(
+        &self,
+        doc_pos: TextDocumentPositionParams,
+    ) -> Option<Vec<CompletionItem>> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).ok()?;
+        let offset = lsp_to_byte(self.lines(&uri)?, doc_pos.position)?;
+        let items = complete(&self.graph, &uri, offset)
+            .into_iter()
+            .map(|completion| {
+                let kind = match completion.kind {
+                    complete::Kind::Module => CompletionItemKind::MODULE,
+                    complete::Kind::Def => CompletionItemKind::FUNCTION,
+                    complete::Kind::Local => CompletionItemKind::VARIABLE,
+                    complete::Kind::Field => CompletionItemKind::FIELD,
+                    complete::Kind::Type => CompletionItemKind::STRUCT,
+                    complete::Kind::TypeParam => CompletionItemKind::TYPE_PARAMETER,
+                };
+                CompletionItem {
+                    label: completion.label,
+                    kind: Some(kind),
+                    detail: completion.detail,
+                    ..Default::default()
+                }
+            });
+        Some(items.collect())
+    }
+
+    fn signature_help_success(&self, doc_pos: TextDocumentPositionParams) -> Option<SignatureHelp> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).ok()?;
+        let offset = lsp_to_byte(self.lines(&uri)?, doc_pos.position)?;
+        let sig = signature::signature_help(&self.graph, &uri, offset)?;
+        // parameter offsets are in the same units as columns in positions
+        let units = |end: usize| match self.encoding {
+            None => end as u32,
+            Some(enc) => enc.measure(&sig.label[..end]) as u32,
+        };
+        let params = (sig.params.iter())
+            .map(|range| ParameterInformation {
+                label: ParameterLabel::LabelOffsets([units(range.start), units(range.end)]),
+                documentation: None,
+            })
+            .collect();
+        Some(SignatureHelp {
+            signatures: vec![SignatureInformation {
+                label: sig.label,
+                documentation: None,
+                parameters: Some(params),
+                active_parameter: Some(sig.active as u32),
+            }],
+            active_signature: Some(0),
+            active_parameter: Some(sig.active as u32),
+        })
+    }
+
+    fn semantic_tokens_success(&self, uri: &lsp_types::Uri) -> Option<SemanticTokens> {
+        let uri = Uri::from_lsp_uri(uri).ok()?;
+        let data = semantic_tokens(self.lines(&uri)?, highlights(&self.graph, &uri)?);
+        let result_id = self.result_id.get();
+        self.result_id.set(result_id + 1);
+        let result_id = result_id.to_string();
+        (self.tokens.borrow_mut()).insert(uri, (result_id.clone(), data.clone()));
+        Some(SemanticTokens {
+            result_id: Some(result_id),
+            data,
+        })
+    }
+
+    fn inlay_hint_success(&self, params: InlayHintParams) -> Option<Vec<InlayHint>> {
+        let uri = Uri::from_lsp_uri(&params.text_document.uri).ok()?;
+        let index = self.lines(&uri)?;
+        // clients sometimes ask for a range that extends past the end of the document
+        let start = lsp_to_byte(index, params.range.start)?;
+        let end = lsp_to_byte(index, params.range.end).unwrap_or(usize::MAX);
+        let items = (hints(&self.graph, &uri)?.into_iter())
+            .filter(|hint| self.config.inlay_hints.show(hint.kind))
+            .filter(|hint| start <= hint.offset && hint.offset <= end)
+            .map(|hint| InlayHint {
+                position: byte_to_lsp(index, hint.offset),
+                label: InlayHintLabel::String(hint.label),
+                kind: Some(InlayHintKind::TYPE),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: None,
+                data: None,
+            });
+        Some(items.collect())
+    }
+
+    fn document_link_success(&self, uri: &lsp_types::Uri) -> Option<Vec<DocumentLink>> {
+        let uri = Uri::from_lsp_uri(uri).ok()?;
+        let index = self.lines(&uri)?;
+        let links =
+            (outline::links(&self.graph, &uri)?.into_iter()).filter_map(|(range, target)| {
+                Some(DocumentLink {
+                    range: bytes_to_lsp(index, range),
+                    target: Some(target.to_lsp_uri().ok()?),
+                    tooltip: None,
+                    data: None,
+                })
+            });
+        Some(links.collect())
+    }
+
+    fn code_lens_success(&self, lsp_uri: &lsp_types::Uri) -> Option<Vec<CodeLens>> {
+        let uri = Uri::from_lsp_uri(lsp_uri).ok()?;
+        let Data::Analyzed { syn, .. } = &self.graph.get(&uri).data else {
+            return None;
+        };
+        let index = self.lines(&uri)?;
+        let lenses = (0..syn.tree.defs().len()).map(|i| {
+            let id = parse::DefId::from_usize(i).unwrap();
+            let symbol = Symbol::Def { src: None, id };
+            let locations: Vec<Location> = (symbol::references(&self.graph, &uri, symbol))
+                .into_iter()
+                .filter_map(|(module, range)| self.location(&module, range))
+                .collect();
+            let title = match locations.len() {
+                1 => "1 reference".to_owned(),
+                n => format!("{n} references"),
+            };
+            let range = bytes_to_lsp(index, syn.toks.get(syn.tree.def(id).name).byte_range());
+            // the client has to turn these back into its own types to show them
+            let arguments = vec![
+                serde_json::to_value(lsp_uri).unwrap(),
+                serde_json::to_value(range.start).unwrap(),
+                serde_json::to_value(locations).unwrap(),
+            ];
+            CodeLens {
+                range,
+                command: Some(Command {
+                    title,
+                    command: SHOW_REFERENCES.to_owned(),
+                    arguments: Some(arguments),
+                }),
+                data: None,
+            }
+        });
+        Some(lenses.collect())
+    }
+
+    fn document_symbol_success(&self, uri: &lsp_types::Uri) -> Option<Vec<DocumentSymbol>> {
+        let uri = Uri::from_lsp_uri(uri).ok()?;
+        let index = self.lines(&uri)?;
+        let items = items(&self.graph, &uri)?.into_iter();
+        Some(items.map(|item| document_symbol(index, item)).collect())
+    }
+
+    fn workspace_symbol_success(&self, query: &str) -> Vec<WorkspaceSymbol> {
+        (workspace(&self.graph, query).into_iter())
+            .filter_map(|(uri, syn, item)| {
+                let location = Location {
+                    uri: uri.to_lsp_uri().ok()?,
+                    range: bytes_to_lsp(self.encode(&syn.src.lines), item.selection),
+                };
+                Some(WorkspaceSymbol {
+                    name: item.name,
+                    kind: symbol_kind(item.kind),
+                    tags: None,
+                    container_name: uri.as_str().rsplit('/').next().map(str::to_owned),
+                    location: OneOf::Left(location),
+                    data: None,
+                })
+            })
+            .collect()
+    }
+
+    fn call_item(&self, def: &call::Def) -> Option<CallHierarchyItem> {
+        let item = call::item(&self.graph, def)?;
+        let lines = self.lines(&def.0)?;
+        Some(CallHierarchyItem {
+            name: item.name,
+            kind: symbol_kind(item.kind),
+            tags: None,
+            detail: item.detail,
+            uri: def.0.to_lsp_uri().ok()?,
+            range: bytes_to_lsp(lines, item.range),
+            selection_range: bytes_to_lsp(lines, item.selection),
+            data: None,
+        })
+    }
+
+    /// The definition that a call hierarchy item was made from.
+    fn call_def(&self, item: &CallHierarchyItem) -> Option<call::Def> {
+        let uri = Uri::from_lsp_uri(&item.uri).ok()?;
+        let offset = lsp_to_byte(self.lines(&uri)?, item.selection_range.start)?;
+        call::def_at(&self.graph, &uri, offset)
+    }
+
+    fn prepare_call_hierarchy_success(
+        &self,
+        doc_pos: TextDocumentPositionParams,
+    ) -> Option<Vec<CallHierarchyItem>> {
+        let uri = Uri::from_lsp_uri(&doc_pos.text_document.uri).ok()?;
+        let offset = lsp_to_byte(self.lines(&uri)?, doc_pos.position)?;
+        let def = call::def_at(&self.graph, &uri, offset)?;
+        Some(vec![self.call_item(&def)?])
+    }
+
+    fn incoming_calls_success(
+        &self,
+        item: &CallHierarchyItem,
+    ) -> Option<Vec<CallHierarchyIncomingCall>> {
+        let def = self.call_def(item)?;
+        let calls = call::incoming(&self.graph, &def).into_iter();
+        Some(
+            calls
+                .filter_map(|(caller, ranges)| {
+                    let lines = self.lines(&caller.0)?;
+                    Some(CallHierarchyIncomingCall {
+                        from: self.call_item(&caller)?,
+                        from_ranges: (ranges.into_iter())
+                            .map(|range| bytes_to_lsp(lines, range))
+                            .collect(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn outgoing_calls_success(
+        &self,
+        item: &CallHierarchyItem,
+    ) -> Option<Vec<CallHierarchyOutgoingCall>> {
+        let def = self.call_def(item)?;
+        let lines = self.lines(&def.0)?;
+        let calls = call::outgoing(&self.graph, &def).into_iter();
+        Some(
+            calls
+                .filter_map(|(callee, ranges)| {
+                    Some(CallHierarchyOutgoingCall {
+                        to: self.call_item(&callee)?,
+                        from_ranges: (ranges.into_iter())
+                            .map(|range| bytes_to_lsp(lines, range))
+                            .collect(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn folding_range_success(&self, uri: &lsp_types::Uri) -> Option<Vec<FoldingRange>> {
+        let uri = Uri::from_lsp_uri(uri).ok()?;
+        let index = self.lines(&uri)?;
+        let ranges = (folds(&self.graph, &uri)?.into_iter()).filter_map(|fold| {
+            let range = bytes_to_lsp(index, fold.range);
+            // a fold on a single line wouldn't hide anything
+            (range.start.line < range.end.line).then(|| FoldingRange {
+                start_line: range.start.line,
+                start_character: None,
+                end_line: range.end.line,
+                end_character: None,
+                kind: fold.comment.then_some(FoldingRangeKind::Comment),
+                collapsed_text: None,
+            })
+        });
+        Some(ranges.collect())
+    }
+
     fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams) -> anyhow::Result<()> {
         let doc = params.text_document;
         let uri = Uri::from_lsp_uri(&doc.uri).unwrap();
         self.graph.make_root(uri.clone());
-        let (pending,) = self.graph.pending().into_iter().collect_tuple().unwrap();
-        assert_eq!(pending, uri);
-        self.graph.set_text(&pending, doc.text);
+        self.graph.set_text(&uri, doc.text);
         self.exhaust();
-        self.diagnose_all()
+        Ok(())
     }
 
     fn did_change_text_document(
@@ -301,26 +1466,266 @@ impl State {
         };
         self.graph.change_text(
             &uri,
+            self.encoding,
             changes.into_iter().skip(n).map(|change| {
                 let (start, end) = lsp_to_linecols(change.range.unwrap());
                 (start, end, change.text)
             }),
         );
         self.exhaust();
-        self.diagnose_all()
+        Ok(())
     }
 
-    fn did_save_text_document(&mut self, _: DidSaveTextDocumentParams) -> anyhow::Result<()> {
+    fn did_change_watched_files(
+        &mut self,
+        params: DidChangeWatchedFilesParams,
+    ) -> anyhow::Result<()> {
+        for change in params.changes {
+            if let Ok(uri) = Uri::from_lsp_uri(&change.uri) {
+                self.graph.reload(&uri);
+            }
+        }
+        self.exhaust();
         Ok(())
     }
 
-    fn did_close_text_document(&mut self, _: DidCloseTextDocumentParams) -> anyhow::Result<()> {
+    fn did_change_configuration(
+        &mut self,
+        params: DidChangeConfigurationParams,
+    ) -> anyhow::Result<()> {
+        // clients that support pulling settings send nothing useful here, just a reminder to pull
+        match params.settings.get(SECTION) {
+            Some(settings) => self.configure(settings.clone()),
+            None => self.fetch_config(),
+        }
+    }
+
+    fn did_save_text_document(&mut self, _: DidSaveTextDocumentParams) -> anyhow::Result<()> {
         Ok(())
     }
 
+    fn did_close_text_document(
+        &mut self,
+        params: DidCloseTextDocumentParams,
+    ) -> anyhow::Result<()> {
+        let uri = Uri::from_lsp_uri(&params.text_document.uri).unwrap();
+        self.tokens.get_mut().remove(&uri);
+        self.graph.remove_root(&uri);
+        self.exhaust();
+        // if it's still imported and has errors, the next diagnostics will show them again
+        self.publish(&uri, vec![])
+    }
+
+    fn completion(&self, params: CompletionParams) -> ResponseResult<Option<CompletionResponse>> {
+        Ok(self
+            .completion_success(params.text_document_position)
+            .map(CompletionResponse::Array))
+    }
+
+    fn signature_help(&self, params: SignatureHelpParams) -> ResponseResult<Option<SignatureHelp>> {
+        Ok(self.signature_help_success(params.text_document_position_params))
+    }
+
+    fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> ResponseResult<Option<SemanticTokensResult>> {
+        Ok(self
+            .semantic_tokens_success(&params.text_document.uri)
+            .map(SemanticTokensResult::Tokens))
+    }
+
+    fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> ResponseResult<Option<SemanticTokensFullDeltaResult>> {
+        let prev = Uri::from_lsp_uri(&params.text_document.uri)
+            .ok()
+            .and_then(|uri| self.tokens.borrow_mut().remove(&uri));
+        let Some(tokens) = self.semantic_tokens_success(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let prev = match prev {
+            Some((id, prev)) if id == params.previous_result_id => prev,
+            _ => return Ok(Some(SemanticTokensFullDeltaResult::Tokens(tokens))),
+        };
+        Ok(Some(SemanticTokensFullDeltaResult::TokensDelta(
+            SemanticTokensDelta {
+                result_id: tokens.result_id,
+                edits: semantic_tokens_edits(&prev, &tokens.data),
+            },
+        )))
+    }
+
+    fn inlay_hint(&self, params: InlayHintParams) -> ResponseResult<Option<Vec<InlayHint>>> {
+        Ok(self.inlay_hint_success(params))
+    }
+
+    fn document_diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> ResponseResult<DocumentDiagnosticReportResult> {
+        let uri = Uri::from_lsp_uri(&params.text_document.uri).ok();
+        let items = match uri.and_then(|uri| self.graph.nodes().find(|(other, _)| **other == uri)) {
+            Some((uri, node)) if !matches!(node.data, Data::Pending) => self.diagnose(uri, node),
+            _ => vec![],
+        };
+        Ok(DocumentDiagnosticReportResult::Report(
+            DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id: None,
+                    items,
+                },
+            }),
+        ))
+    }
+
+    fn workspace_diagnostic(
+        &self,
+        _: WorkspaceDiagnosticParams,
+    ) -> ResponseResult<WorkspaceDiagnosticReportResult> {
+        let items = (self.diagnose_imported().into_iter())
+            .filter_map(|(uri, _, items)| {
+                let report = WorkspaceFullDocumentDiagnosticReport {
+                    uri: uri.to_lsp_uri().ok()?,
+                    version: None,
+                    full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                        result_id: None,
+                        items,
+                    },
+                };
+                Some(WorkspaceDocumentDiagnosticReport::Full(report))
+            })
+            .collect();
+        Ok(WorkspaceDiagnosticReportResult::Report(
+            WorkspaceDiagnosticReport { items },
+        ))
+    }
+
+    fn document_link(
+        &self,
+        params: DocumentLinkParams,
+    ) -> ResponseResult<Option<Vec<DocumentLink>>> {
+        Ok(self.document_link_success(&params.text_document.uri))
+    }
+
+    fn code_lens(&self, params: CodeLensParams) -> ResponseResult<Option<Vec<CodeLens>>> {
+        Ok(self.code_lens_success(&params.text_document.uri))
+    }
+
+    fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> ResponseResult<Option<DocumentSymbolResponse>> {
+        Ok(self
+            .document_symbol_success(&params.text_document.uri)
+            .map(DocumentSymbolResponse::Nested))
+    }
+
+    fn workspace_symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> ResponseResult<Option<WorkspaceSymbolResponse>> {
+        Ok(Some(WorkspaceSymbolResponse::Nested(
+            self.workspace_symbol_success(&params.query),
+        )))
+    }
+
+    fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> ResponseResult<Option<Vec<CallHierarchyItem>>> {
+        Ok(self.prepare_call_hierarchy_success(params.text_document_position_params))
+    }
+
+    fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> ResponseResult<Option<Vec<CallHierarchyIncomingCall>>> {
+        Ok(self.incoming_calls_success(&params.item))
+    }
+
+    fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> ResponseResult<Option<Vec<CallHierarchyOutgoingCall>>> {
+        Ok(self.outgoing_calls_success(&params.item))
+    }
+
+    fn folding_range(
+        &self,
+        params: FoldingRangeParams,
+    ) -> ResponseResult<Option<Vec<FoldingRange>>> {
+        Ok(self.folding_range_success(&params.text_document.uri))
+    }
+
     fn hover(&self, params: HoverParams) -> ResponseResult<Option<Hover>> {
         Ok(self.hover_success(params.text_document_position_params))
     }
+
+    fn definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> ResponseResult<Option<GotoDefinitionResponse>> {
+        Ok(self
+            .definition_success(params.text_document_position_params)
+            .map(GotoDefinitionResponse::Scalar))
+    }
+
+    fn references(&self, params: ReferenceParams) -> ResponseResult<Option<Vec<Location>>> {
+        Ok(self.references_success(
+            params.text_document_position,
+            params.context.include_declaration,
+        ))
+    }
+
+    fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> ResponseResult<Option<Vec<DocumentHighlight>>> {
+        Ok(self.highlight_success(params.text_document_position_params))
+    }
+
+    fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> ResponseResult<Option<PrepareRenameResponse>> {
+        self.prepare_rename_success(params)
+            .map(|range| Some(PrepareRenameResponse::Range(range)))
+            .map_err(request_failed)
+    }
+
+    fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> ResponseResult<Option<Vec<CodeActionOrCommand>>> {
+        Ok(self.code_action_success(params))
+    }
+
+    fn formatting(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> ResponseResult<Option<Vec<TextEdit>>> {
+        self.formatting_success(&params.text_document.uri, None)
+            .map(Some)
+            .map_err(request_failed)
+    }
+
+    fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> ResponseResult<Option<Vec<TextEdit>>> {
+        self.formatting_success(&params.text_document.uri, Some(params.range))
+            .map(Some)
+            .map_err(request_failed)
+    }
+
+    fn rename(&self, params: RenameParams) -> ResponseResult<Option<WorkspaceEdit>> {
+        self.rename_success(params.text_document_position, &params.new_name)
+            .map(Some)
+            .map_err(request_failed)
+    }
 }
 
 type RequestHandler = Box<dyn Fn(&State, RequestId, Value) -> anyhow::Result<()>>;
@@ -401,30 +1806,204 @@ impl Notifications {
 }
 
 fn run(stdlib: Uri, connection: &Connection) -> anyhow::Result<()> {
-    let reqs = Requests::new().with::<HoverRequest>(State::hover);
+    let reqs = Requests::new()
+        .with::<CallHierarchyIncomingCalls>(State::incoming_calls)
+        .with::<CallHierarchyOutgoingCalls>(State::outgoing_calls)
+        .with::<CallHierarchyPrepare>(State::prepare_call_hierarchy)
+        .with::<CodeActionRequest>(State::code_action)
+        .with::<CodeLensRequest>(State::code_lens)
+        .with::<Completion>(State::completion)
+        .with::<DocumentDiagnosticRequest>(State::document_diagnostic)
+        .with::<DocumentHighlightRequest>(State::document_highlight)
+        .with::<DocumentLinkRequest>(State::document_link)
+        .with::<DocumentSymbolRequest>(State::document_symbol)
+        .with::<FoldingRangeRequest>(State::folding_range)
+        .with::<Formatting>(State::formatting)
+        .with::<GotoDefinition>(State::definition)
+        .with::<HoverRequest>(State::hover)
+        .with::<InlayHintRequest>(State::inlay_hint)
+        .with::<PrepareRenameRequest>(State::prepare_rename)
+        .with::<RangeFormatting>(State::range_formatting)
+        .with::<References>(State::references)
+        .with::<Rename>(State::rename)
+        .with::<SemanticTokensFullDeltaRequest>(State::semantic_tokens_full_delta)
+        .with::<SemanticTokensFullRequest>(State::semantic_tokens_full)
+        .with::<SignatureHelpRequest>(State::signature_help)
+        .with::<WorkspaceDiagnosticRequest>(State::workspace_diagnostic)
+        .with::<WorkspaceSymbolRequest>(State::workspace_symbol);
     let nots = Notifications::new()
+        .with::<DidChangeConfiguration>(State::did_change_configuration)
         .with::<DidChangeTextDocument>(State::did_change_text_document)
+        .with::<DidChangeWatchedFiles>(State::did_change_watched_files)
         .with::<DidCloseTextDocument>(State::did_close_text_document)
         .with::<DidOpenTextDocument>(State::did_open_text_document)
         .with::<DidSaveTextDocument>(State::did_save_text_document);
-    connection.initialize(serde_json::to_value(&ServerCapabilities {
+    let (id, params) = connection.initialize_start()?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    let encoding = position_encoding(&params.capabilities);
+    let capabilities = ServerCapabilities {
+        position_encoding: Some(match encoding {
+            None => PositionEncodingKind::UTF8,
+            Some(_) => PositionEncodingKind::UTF16,
+        }),
         text_document_sync: Some(TextDocumentSyncCapability::Kind(
             TextDocumentSyncKind::INCREMENTAL,
         )),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        code_lens_provider: Some(CodeLensOptions {
+            resolve_provider: Some(false),
+        }),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["\"".to_owned(), "{".to_owned()]),
+            ..Default::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+            identifier: Some(SECTION.to_owned()),
+            inter_file_dependencies: true,
+            workspace_diagnostics: true,
+            ..Default::default()
+        })),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_highlight_provider: Some(OneOf::Left(true)),
+        document_link_provider: Some(DocumentLinkOptions {
+            resolve_provider: Some(false),
+            work_done_progress_options: Default::default(),
+        }),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
         hover_provider: Some(HoverProviderCapability::Simple(true)),
+        inlay_hint_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: Default::default(),
+        })),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: TOKEN_MODIFIERS.to_vec(),
+                },
+                full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                ..Default::default()
+            },
+        )),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_owned(), ",".to_owned()]),
+            retrigger_characters: Some(vec![" ".to_owned()]),
+            ..Default::default()
+        }),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
         ..Default::default()
-    })?)?;
-    let mut state = State::new(stdlib, connection.sender.clone());
-    for msg in &connection.receiver {
-        match msg {
-            Message::Request(req) => {
-                if connection.handle_shutdown(&req)? {
-                    break;
+    };
+    let result = InitializeResult {
+        capabilities,
+        server_info: None,
+    };
+    connection.initialize_finish(id, serde_json::to_value(result)?)?;
+    let (jobs, pending) = unbounded();
+    let (done, results) = unbounded();
+    for _ in 0..thread::available_parallelism().map_or(1, NonZeroUsize::get) {
+        let (pending, done) = (pending.clone(), done.clone());
+        thread::spawn(move || work(pending, done));
+    }
+    let folders = folders(&params);
+    let mut state = State::new(
+        stdlib,
+        connection.sender.clone(),
+        jobs,
+        encoding,
+        params.capabilities,
+        folders,
+    );
+    state.watch_files()?;
+    // from then on, standard library modules can be read without writing them again
+    if let Err(err) = install(&state.stdlib) {
+        state.warn(err)?;
+    }
+    if let Some(settings) = params.initialization_options {
+        state.configure(settings)?;
+    }
+    state.fetch_config()?;
+    // requests that have to wait for analysis to finish before they can be answered
+    let mut deferred: Vec<lsp_server::Request> = vec![];
+    let respond = |req: lsp_server::Request, code: ErrorCode, message: &str| {
+        let response = lsp_server::Response::new_err(req.id, code as i32, message.to_owned());
+        connection.sender.send(Message::Response(response))
+    };
+    loop {
+        // diagnostics for modules that are still being analyzed would just flicker
+        let timer = match state.publish {
+            Some(at) if state.running.is_empty() => {
+                after(at.saturating_duration_since(Instant::now()))
+            }
+            _ => never(),
+        };
+        select! {
+            recv(connection.receiver) -> msg => match msg {
+                Ok(Message::Request(req)) => {
+                    if connection.handle_shutdown(&req)? {
+                        break;
+                    }
+                    deferred.push(req);
+                }
+                // other requests we send just need an answer, not anything in it
+                Ok(Message::Response(res)) => {
+                    if state.config_request.as_ref() == Some(&res.id) {
+                        state.config_request = None;
+                        let settings = match res.result {
+                            Some(Value::Array(mut items)) if !items.is_empty() => {
+                                items.swap_remove(0)
+                            }
+                            _ => Value::Null,
+                        };
+                        state.configure(settings)?;
+                    }
                 }
-                reqs.handle(&state, req)?;
+                Ok(Message::Notification(not)) if not.method == Cancel::METHOD => {
+                    let id = match serde_json::from_value::<CancelParams>(not.params)?.id {
+                        NumberOrString::Number(n) => RequestId::from(n),
+                        NumberOrString::String(s) => RequestId::from(s),
+                    };
+                    if let Some(i) = deferred.iter().position(|req| req.id == id) {
+                        respond(deferred.remove(i), ErrorCode::RequestCanceled, "canceled")?;
+                    }
+                }
+                Ok(Message::Notification(not)) => {
+                    // positions in a waiting request would be wrong once its document changes
+                    let changed = match not.method.as_str() {
+                        DidChangeTextDocument::METHOD | DidCloseTextDocument::METHOD => {
+                            document(&not.params)
+                        }
+                        _ => None,
+                    };
+                    if let Some(uri) = changed {
+                        let (stale, rest) = (take(&mut deferred).into_iter())
+                            .partition(|req| document(&req.params).as_ref() == Some(&uri));
+                        deferred = rest;
+                        for req in stale {
+                            respond(req, ErrorCode::ContentModified, "document changed")?;
+                        }
+                    }
+                    nots.handle(&mut state, not)?;
+                }
+                Err(_) => break,
+            },
+            recv(results) -> res => state.supply(res?),
+            recv(timer) -> _ => {
+                state.publish = None;
+                state.diagnose_all()?;
+                state.refresh_lenses()?;
             }
-            Message::Response(_) => unreachable!(),
-            Message::Notification(not) => nots.handle(&mut state, not)?,
+        }
+        let (waiting, ready) =
+            (take(&mut deferred).into_iter()).partition(|req| state.waiting(req));
+        deferred = waiting;
+        for req in ready {
+            reqs.handle(&state, req)?;
         }
     }
     Ok(())
@@ -454,3 +2033,481 @@ pub fn language_server(stdlib: Uri) -> Result<(), ()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use lsp_types::{
+        TextDocumentContentChangeEvent, TextDocumentItem, VersionedTextDocumentIdentifier,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    /// A server for a client with no capabilities, along with the messages it sends and the modules
+    /// it wants typechecked, since no workers are running to do that.
+    fn server() -> (State, Receiver<Message>, Receiver<Job>) {
+        let (sender, messages) = unbounded();
+        let (jobs, pending) = unbounded();
+        let stdlib = Uri::from_directory_path("/std").unwrap();
+        let client = ClientCapabilities::default();
+        let state = State::new(stdlib, sender, jobs, None, client, vec![]);
+        (state, messages, pending)
+    }
+
+    fn lsp_uri(path: &str) -> lsp_types::Uri {
+        Uri::from_file_path(path).unwrap().to_lsp_uri().unwrap()
+    }
+
+    fn open(state: &mut State, path: &str, text: &str) {
+        let text_document = TextDocumentItem {
+            uri: lsp_uri(path),
+            language_id: "adroit".to_owned(),
+            version: 0,
+            text: text.to_owned(),
+        };
+        (state.did_open_text_document(DidOpenTextDocumentParams { text_document })).unwrap();
+    }
+
+    fn change(state: &mut State, path: &str, text: &str) {
+        let params = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: lsp_uri(path),
+                version: 1,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_owned(),
+            }],
+        };
+        state.did_change_text_document(params).unwrap();
+    }
+
+    fn position(path: &str, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: lsp_uri(path) },
+            position: Position::new(line, character),
+        }
+    }
+
+    /// Open a module importing another one that then gets a type error, so the importer keeps its
+    /// types from before the change.
+    fn broken_import(state: &mut State, pending: &Receiver<Job>) {
+        open(state, "/b.adroit", "def f: Int = 1\n");
+        open(
+            state,
+            "/a.adroit",
+            "import \"./b\" use f\n\ndef g: Int = f\n",
+        );
+        settle(state, pending);
+        change(state, "/b.adroit", "def f: Int = 1.5\n");
+    }
+
+    /// Typecheck everything the server is waiting on, as the workers would.
+    fn settle(state: &mut State, pending: &Receiver<Job>) {
+        while let Ok(job) = pending.try_recv() {
+            let ((_, syn, deps), canceled) = &job;
+            if canceled.load(Ordering::Relaxed) {
+                continue;
+            }
+            let imports = deps.iter().map(|(_, dep)| dep.as_ref()).collect();
+            let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, imports);
+            state.supply((job, Arc::new(module), errs));
+        }
+    }
+
+    fn configure(state: &mut State, settings: Value) {
+        let params = DidChangeConfigurationParams { settings };
+        state.did_change_configuration(params).unwrap();
+    }
+
+    fn pull(state: &State, path: &str) -> Vec<Diagnostic> {
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri: lsp_uri(path) },
+            identifier: None,
+            previous_result_id: None,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        match state.document_diagnostic(params).unwrap() {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                report.full_document_diagnostic_report.items
+            }
+            report => panic!("expected a full report: {report:?}"),
+        }
+    }
+
+    fn token(delta_line: u32, delta_start: u32, length: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    fn ints(tokens: &[SemanticToken]) -> Vec<u32> {
+        (tokens.iter())
+            .flat_map(|t| {
+                [
+                    t.delta_line,
+                    t.delta_start,
+                    t.length,
+                    t.token_type,
+                    t.token_modifiers_bitset,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_semantic_tokens_edits() {
+        let (a, b, c, d) = (
+            token(0, 4, 1),
+            token(1, 2, 3),
+            token(0, 5, 1),
+            token(2, 0, 4),
+        );
+        let cases: [(&[SemanticToken], &[SemanticToken]); 8] = [
+            (&[a, b, c], &[a, b, c]),
+            (&[a, b, c], &[a, d, c]),
+            (&[a, b, c], &[a, b, d, c]),
+            (&[a, b, c], &[a, c]),
+            (&[a, b, c], &[]),
+            (&[], &[a, b]),
+            (&[a, a], &[a, a, a]),
+            (&[a, b, a], &[a]),
+        ];
+        for (prev, data) in cases {
+            let edits = semantic_tokens_edits(prev, data);
+            let mut applied = ints(prev);
+            for edit in edits.iter().rev() {
+                let start = edit.start as usize;
+                let end = start + edit.delete_count as usize;
+                applied.splice(start..end, ints(edit.data.as_deref().unwrap_or_default()));
+            }
+            assert_eq!(applied, ints(data), "{prev:?} -> {data:?}");
+            assert!(edits.len() <= 1);
+        }
+        assert_eq!(semantic_tokens_edits(&[a, b, c], &[a, b, c]), []);
+    }
+
+    #[test]
+    fn test_did_change_configuration() {
+        let (mut state, messages, _pending) = server();
+        configure(
+            &mut state,
+            json!({
+                "adroit": {
+                    "warnings": { "imports": false },
+                    "inlayHints": { "resultTypes": false, "typeArguments": false },
+                },
+            }),
+        );
+        assert!(!state.config.warnings.imports);
+        assert!(state.config.inlay_hints.show(hint::Kind::Param));
+        assert!(!state.config.inlay_hints.show(hint::Kind::Result));
+        assert!(!state.config.inlay_hints.show(hint::Kind::TypeArgs));
+        assert!(messages.try_recv().is_err());
+
+        // invalid settings are reported and otherwise ignored
+        configure(
+            &mut state,
+            json!({ "adroit": { "warnings": { "imports": "no" } } }),
+        );
+        assert!(!state.config.warnings.imports);
+        match messages.try_recv() {
+            Ok(Message::Notification(not)) => assert_eq!(not.method, ShowMessage::METHOD),
+            msg => panic!("expected a message to show: {msg:?}"),
+        }
+
+        configure(&mut state, json!({ "adroit": null }));
+        assert!(state.config.warnings.imports);
+        assert!(state.config.inlay_hints.show(hint::Kind::Result));
+    }
+
+    #[test]
+    fn test_document_diagnostic() {
+        let (mut state, _messages, pending) = server();
+        open(&mut state, "/b.adroit", "def f: Int = 1.5\n");
+        open(
+            &mut state,
+            "/a.adroit",
+            "import \"./b\" use f\n\ndef g: Int = f\n",
+        );
+        settle(&mut state, &pending);
+        let errors = pull(&state, "/b.adroit");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, None);
+        assert_eq!(
+            pull(&state, "/a.adroit"),
+            [Diagnostic {
+                severity: Some(DiagnosticSeverity::WARNING),
+                ..Diagnostic::new_simple(
+                    lsp_types::Range::new(Position::new(0, 7), Position::new(0, 12)),
+                    "imported module has errors".to_owned(),
+                )
+            }],
+        );
+        assert_eq!(pull(&state, "/c.adroit"), []);
+
+        configure(
+            &mut state,
+            json!({ "adroit": { "warnings": { "imports": false } } }),
+        );
+        assert_eq!(pull(&state, "/a.adroit"), []);
+        assert_eq!(pull(&state, "/b.adroit"), errors);
+    }
+
+    #[test]
+    fn test_config_request() {
+        let (mut state, messages, _pending) = server();
+        state.client =
+            serde_json::from_value(json!({ "workspace": { "configuration": true } })).unwrap();
+        let mut ids = vec![];
+        for _ in 0..2 {
+            configure(&mut state, json!({}));
+            match messages.try_recv() {
+                Ok(Message::Request(req)) => {
+                    assert_eq!(req.method, WorkspaceConfiguration::METHOD);
+                    ids.push(req.id);
+                }
+                msg => panic!("expected a request for settings: {msg:?}"),
+            }
+        }
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(state.config_request.as_ref(), Some(&ids[1]));
+    }
+
+    #[test]
+    fn test_definition_broken_import() {
+        let (mut state, _messages, pending) = server();
+        open(&mut state, "/b.adroit", "def f: Int = 1\n");
+        open(
+            &mut state,
+            "/a.adroit",
+            "import \"./b\" use f\n\ndef g: Int = f\n",
+        );
+        settle(&mut state, &pending);
+        let location = state.definition_success(position("/a.adroit", 2, 13));
+        assert_eq!(location.unwrap().uri, lsp_uri("/b.adroit"));
+
+        change(&mut state, "/b.adroit", "def f: Int = 1.5\n");
+        // the import is only parsed until it has been typechecked again
+        assert_eq!(state.definition_success(position("/a.adroit", 0, 17)), None);
+        settle(&mut state, &pending);
+        assert_eq!(state.definition_success(position("/a.adroit", 0, 17)), None);
+        assert_eq!(state.definition_success(position("/a.adroit", 2, 13)), None);
+        let location = state.definition_success(position("/a.adroit", 2, 4));
+        assert_eq!(location.unwrap().uri, lsp_uri("/a.adroit"));
+    }
+
+    #[test]
+    fn test_references_broken_import() {
+        let (mut state, _messages, pending) = server();
+        broken_import(&mut state, &pending);
+        settle(&mut state, &pending);
+        // uses in the importer can't be matched up with the definition until it's checked again
+        let locations = state.references_success(position("/b.adroit", 0, 4), true);
+        let uris: Vec<_> = locations.unwrap().into_iter().map(|loc| loc.uri).collect();
+        assert_eq!(uris, [lsp_uri("/b.adroit")]);
+        assert_eq!(
+            state.references_success(position("/a.adroit", 2, 13), true),
+            None
+        );
+        assert_eq!(state.highlight_success(position("/a.adroit", 0, 17)), None);
+        assert_eq!(state.highlight_success(position("/a.adroit", 2, 13)), None);
+    }
+
+    #[test]
+    fn test_hover_broken_import() {
+        let (mut state, _messages, pending) = server();
+        broken_import(&mut state, &pending);
+        settle(&mut state, &pending);
+        // the importer's own types are still shown, just not the imported definition
+        let hover = state.hover_success(position("/a.adroit", 2, 13)).unwrap();
+        assert_eq!(
+            hover.contents,
+            HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: "```\nInt\n```".to_owned(),
+            }),
+        );
+        assert!(state.hover_success(position("/a.adroit", 0, 17)).is_none());
+    }
+
+    #[test]
+    fn test_cancel_outdated() {
+        let (mut state, _messages, pending) = server();
+        open(&mut state, "/b.adroit", "def f: Int = 1\n");
+        change(&mut state, "/b.adroit", "def f: Int = 2\n");
+        let jobs: Vec<Job> = pending.try_iter().collect();
+        let canceled: Vec<bool> = (jobs.iter())
+            .map(|(_, canceled)| canceled.load(Ordering::Relaxed))
+            .collect();
+        assert_eq!(canceled, [true, false]);
+        assert_eq!(state.running.len(), 1);
+
+        // a result that comes in anyway is ignored rather than counted twice
+        for job in jobs {
+            let (_, syn, _) = &job.0;
+            let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, vec![]);
+            state.supply((job, Arc::new(module), errs));
+        }
+        assert!(state.running.is_empty());
+        let uri = Uri::from_file_path("/b.adroit").unwrap();
+        let Data::Analyzed { syn, .. } = &state.graph.get(&uri).data else {
+            panic!("module should be analyzed");
+        };
+        assert_eq!(syn.src.text, "def f: Int = 2\n");
+    }
+
+    #[test]
+    fn test_waiting_on_imports() {
+        let (mut state, _messages, pending) = server();
+        open(&mut state, "/b.adroit", "def f: Int = 1\n");
+        open(
+            &mut state,
+            "/a.adroit",
+            "import \"./b\" use f\n\ndef g: Int = f\n",
+        );
+        settle(&mut state, &pending);
+        let req = lsp_server::Request {
+            id: RequestId::from(0),
+            method: HoverRequest::METHOD.to_owned(),
+            params: serde_json::to_value(position("/a.adroit", 2, 13)).unwrap(),
+        };
+        assert!(!state.waiting(&req));
+        // the document itself is unchanged, but its types might not be once the import is checked
+        change(&mut state, "/b.adroit", "def f: Int = 2\n");
+        assert!(state.waiting(&req));
+        settle(&mut state, &pending);
+        assert!(!state.waiting(&req));
+    }
+
+    #[test]
+    fn test_document_link() {
+        let (mut state, _messages, _pending) = server();
+        open(
+            &mut state,
+            "/a.adroit",
+            "import \"./b\" use f\nimport \"x/y\" use g\n",
+        );
+        let links = state.document_link_success(&lsp_uri("/a.adroit")).unwrap();
+        let links: Vec<_> = (links.into_iter())
+            .map(|link| (link.range, link.target.unwrap()))
+            .collect();
+        // the link is just the path, not the quotes around it
+        let range = |line| lsp_types::Range::new(Position::new(line, 8), Position::new(line, 11));
+        assert_eq!(
+            links,
+            [
+                (range(0), lsp_uri("/b.adroit")),
+                (range(1), lsp_uri("/std/x/y.adroit")),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_code_lens() {
+        let (mut state, _messages, pending) = server();
+        broken_import(&mut state, &pending);
+        change(
+            &mut state,
+            "/b.adroit",
+            "def f: Int = 1\n\ndef h: Int = f\n",
+        );
+        settle(&mut state, &pending);
+        let lenses = state.code_lens_success(&lsp_uri("/b.adroit")).unwrap();
+        let titles: Vec<_> = (lenses.into_iter())
+            .map(|lens| (lens.range.start.line, lens.command.unwrap().title))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                (0, "3 references".to_owned()),
+                (2, "0 references".to_owned())
+            ],
+        );
+
+        // uses in an importer that hasn't been checked against the new version don't count
+        change(&mut state, "/b.adroit", "def f: Int = 1.5\n");
+        settle(&mut state, &pending);
+        let lenses = state.code_lens_success(&lsp_uri("/b.adroit")).unwrap();
+        assert_eq!(lenses[0].command.as_ref().unwrap().title, "0 references");
+    }
+
+    #[test]
+    fn test_rename_unopened_importer() {
+        let (mut state, _messages, pending) = server();
+        let dir = env::temp_dir().join(format!("adroit-rename-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let importer = dir.join("a.adroit");
+        fs::write(&importer, "import \"./b\" use f\n\ndef g: Int = f\n").unwrap();
+        state.folders = vec![dir.clone()];
+        let path = dir.join("b.adroit");
+        let path = path.to_str().unwrap();
+        open(&mut state, path, "def f: Int = 1\n");
+        settle(&mut state, &pending);
+        let edit = state.rename_success(position(path, 0, 4), "h");
+        fs::remove_dir_all(&dir).unwrap();
+        // the importer is read from disk just for this, without opening it
+        let mut changes: Vec<_> = (edit.unwrap().changes.unwrap().into_iter())
+            .map(|(uri, edits)| (uri.as_str().to_owned(), edits.len()))
+            .collect();
+        changes.sort();
+        let key = |path: &PathBuf| lsp_uri(path.to_str().unwrap()).as_str().to_owned();
+        assert_eq!(
+            changes,
+            [(key(&importer), 2), (key(&dir.join("b.adroit")), 1)]
+        );
+        assert!(state
+            .graph
+            .nodes()
+            .all(|(uri, _)| uri.as_str() != key(&importer)));
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        let index = LineIndex::new("# é😀 x\n");
+        let lines = Lines {
+            index: &index,
+            wide: Some(WideEncoding::Utf16),
+        };
+        // `é` is one UTF-16 unit but two bytes, and `😀` is two units but four bytes
+        assert_eq!(byte_to_lsp(lines, 9), Position::new(0, 6));
+        assert_eq!(lsp_to_byte(lines, Position::new(0, 6)), Some(9));
+        let range = bytes_to_lsp(lines, 2..8);
+        assert_eq!(
+            range,
+            lsp_types::Range::new(Position::new(0, 2), Position::new(0, 5))
+        );
+
+        let (sender, _messages) = unbounded();
+        let (jobs, pending) = unbounded();
+        let stdlib = Uri::from_directory_path("/std").unwrap();
+        let client = ClientCapabilities::default();
+        let mut state = State::new(
+            stdlib,
+            sender,
+            jobs,
+            Some(WideEncoding::Utf16),
+            client,
+            vec![],
+        );
+        open(&mut state, "/é😀.adroit", "def f: Int = 1\n");
+        open(&mut state, "/a.adroit", "import \"./é😀\" use f\n");
+        settle(&mut state, &pending);
+        let links = state.document_link_success(&lsp_uri("/a.adroit")).unwrap();
+        let range = lsp_types::Range::new(Position::new(0, 8), Position::new(0, 13));
+        assert_eq!(links[0].range, range);
+        let location = state
+            .definition_success(position("/a.adroit", 0, 19))
+            .unwrap();
+        assert_eq!(location.uri, lsp_uri("/é😀.adroit"));
+        assert_eq!(location.range.start, Position::new(0, 4));
+    }
+}
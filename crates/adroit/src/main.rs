@@ -1,12 +1,23 @@
+mod action;
+mod call;
 mod cli;
 mod compile;
+mod complete;
+mod doc;
 mod fetch;
+mod format;
 mod graph;
+mod highlight;
+mod hint;
 mod lex;
 mod lsp;
+mod outline;
 mod parse;
 mod pprint;
 mod range;
+mod rename;
+mod signature;
+mod symbol;
 mod typecheck;
 mod util;
 
@@ -0,0 +1,381 @@
+use std::{collections::HashSet, ops::Range, sync::Arc};
+
+use crate::{
+    compile::{FullModule, GraphImporter, Printer},
+    complete::exports,
+    fetch::{builtins, read},
+    graph::{Data, Graph, Source, Syntax, Uri},
+    lex::{lex, TokenId, TokenKind},
+    parse::{self, parse, Bind, Expr},
+    range::{def_range, expr_range, import_range, param_range},
+    util::Id,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Module,
+    Def,
+}
+
+/// A named part of a module, for navigating to it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Item {
+    pub name: String,
+    pub kind: Kind,
+    pub detail: Option<String>,
+
+    /// The whole import or definition.
+    pub range: Range<usize>,
+
+    /// Just the name.
+    pub selection: Range<usize>,
+
+    pub children: Vec<Item>,
+}
+
+/// A region of a module that can be collapsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Fold {
+    pub range: Range<usize>,
+    pub comment: bool,
+}
+
+/// The parsed syntax of a module in the graph, if any.
+fn syntax(graph: &Graph, uri: &Uri) -> Option<Arc<Syntax>> {
+    match &graph.get(uri).data {
+        Data::Parsed { syn } | Data::Analyzed { syn, .. } => Some(Arc::clone(syn)),
+        _ => None,
+    }
+}
+
+/// The imports and definitions in a module, with types if it has been analyzed.
+pub fn items(graph: &Graph, uri: &Uri) -> Option<Vec<Item>> {
+    let syn = syntax(graph, uri)?;
+    let (source, tokens, tree) = (&syn.src.text, &syn.toks, &syn.tree);
+    let text = |id: TokenId| &source[tokens.get(id).byte_range()];
+    let uris = graph.imports(uri).ok();
+    let mut items = vec![];
+    for (i, import) in tree.imports().iter().enumerate() {
+        let uri = uris.as_ref().map(|uris| &uris[i]);
+        let types = uri.map(|uri| exports(graph, uri)).unwrap_or_default();
+        let children = (import.names.iter())
+            .map(|&name| Item {
+                name: text(name).to_owned(),
+                kind: Kind::Def,
+                detail: types.get(text(name)).cloned(),
+                range: tokens.get(name).byte_range(),
+                selection: tokens.get(name).byte_range(),
+                children: vec![],
+            })
+            .collect();
+        items.push(Item {
+            name: tokens.get(import.module).string(source),
+            kind: Kind::Module,
+            detail: None,
+            range: import_range(tokens, tree, import),
+            selection: tokens.get(import.module).byte_range(),
+            children,
+        });
+    }
+    let printer = match &graph.get(uri).data {
+        Data::Analyzed { syn, sem, deps, .. } => {
+            let full = FullModule {
+                source: &syn.src.text,
+                tokens: &syn.toks,
+                tree: &syn.tree,
+                module: Arc::clone(sem),
+            };
+            Some((sem, Printer::new(full, GraphImporter { graph, deps })))
+        }
+        _ => None,
+    };
+    for (i, def) in tree.defs().iter().enumerate() {
+        let id = parse::DefId::from_usize(i).unwrap();
+        let detail = (printer.as_ref())
+            .map(|(sem, printer)| printer.ty(sem.val(sem.def(id)).ty).to_string());
+        items.push(Item {
+            name: text(def.name).to_owned(),
+            kind: Kind::Def,
+            detail,
+            range: def_range(tokens, tree, id).unwrap(),
+            selection: tokens.get(def.name).byte_range(),
+            children: vec![],
+        });
+    }
+    Some(items)
+}
+
+/// Whether every character of a query appears in a name, in order, ignoring case.
+fn matches(query: &str, name: &str) -> bool {
+    let mut chars = name.chars().flat_map(char::to_lowercase);
+    (query.chars().flat_map(char::to_lowercase)).all(|c| chars.any(|d| d == c))
+}
+
+/// The definitions matching a query in every module in the graph and the standard library.
+pub fn workspace(graph: &Graph, query: &str) -> Vec<(Uri, Arc<Syntax>, Item)> {
+    let mut modules: Vec<(Uri, Arc<Syntax>)> = (graph.nodes())
+        .filter_map(|(uri, _)| Some((uri.clone(), syntax(graph, uri)?)))
+        .collect();
+    for name in builtins() {
+        let uri = Uri::resolve(graph.stdlib(), graph.stdlib(), name).unwrap();
+        if modules.iter().any(|(other, _)| *other == uri) {
+            continue;
+        }
+        // the language server already wrote these to disk, so the client can open them
+        let Ok(text) = read(graph.stdlib(), &uri) else {
+            continue;
+        };
+        let toks = lex(&text).unwrap();
+        let tree = parse(&toks).unwrap();
+        let src = Source::new(text);
+        modules.push((uri, Arc::new(Syntax { src, toks, tree })));
+    }
+    let mut found = vec![];
+    for (uri, syn) in modules {
+        for (i, def) in syn.tree.defs().iter().enumerate() {
+            let id = parse::DefId::from_usize(i).unwrap();
+            let range = syn.toks.get(def.name).byte_range();
+            let name = &syn.src.text[range.clone()];
+            if matches(query, name) {
+                let item = Item {
+                    name: name.to_owned(),
+                    kind: Kind::Def,
+                    detail: None,
+                    range: def_range(&syn.toks, &syn.tree, id).unwrap(),
+                    selection: range,
+                    children: vec![],
+                };
+                found.push((uri.clone(), Arc::clone(&syn), item));
+            }
+        }
+    }
+    found
+}
+
+/// The contents of each import string in a module, along with the module it refers to.
+pub fn links(graph: &Graph, uri: &Uri) -> Option<Vec<(Range<usize>, Uri)>> {
+    let syn = syntax(graph, uri)?;
+    let (source, tokens, tree) = (&syn.src.text, &syn.toks, &syn.tree);
+    let links = (tree.imports().iter()).filter_map(|import| {
+        let token = tokens.get(import.module);
+        let target = uri.resolve(graph.stdlib(), &token.string(source)).ok()?;
+        // just inside the quotes, which are always one byte each
+        let range = token.byte_range();
+        Some((range.start + 1..range.end - 1, target))
+    });
+    Some(links.collect())
+}
+
+/// The definitions, `let` chains, records and comment blocks in a module.
+pub fn folds(graph: &Graph, uri: &Uri) -> Option<Vec<Fold>> {
+    let syn = syntax(graph, uri)?;
+    let (source, tokens, tree) = (&syn.src, &syn.toks, &syn.tree);
+    let mut folds = vec![];
+    let mut fold = |range: Option<Range<usize>>| {
+        if let Some(range) = range {
+            let comment = false;
+            folds.push(Fold { range, comment });
+        }
+    };
+    for i in 0..tree.defs().len() {
+        fold(def_range(
+            tokens,
+            tree,
+            parse::DefId::from_usize(i).unwrap(),
+        ));
+    }
+    let mut bodies = HashSet::new();
+    let mut rests = HashSet::new();
+    for expr in tree.exprs() {
+        match *expr {
+            Expr::Let { body, .. } | Expr::Index { body, .. } => {
+                bodies.insert(body);
+            }
+            Expr::Record { rest, .. } => {
+                rests.insert(rest);
+            }
+            _ => {}
+        }
+    }
+    for (i, expr) in tree.exprs().iter().enumerate() {
+        let id = parse::ExprId::from_usize(i).unwrap();
+        match *expr {
+            // the result at the end of a chain stays visible
+            Expr::Let { .. } | Expr::Index { .. } if !bodies.contains(&id) => {
+                let mut last = id;
+                let mut end = id;
+                while let Expr::Let { val, body, .. } | Expr::Index { val, body, .. } =
+                    tree.expr(last)
+                {
+                    (last, end) = (body, val);
+                }
+                let start = expr_range(tokens, tree, id).map(|range| range.start);
+                let end = expr_range(tokens, tree, end).map(|range| range.end);
+                fold(start.zip(end).map(|(start, end)| start..end));
+            }
+            Expr::Record { .. } if !rests.contains(&id) => fold(expr_range(tokens, tree, id)),
+            _ => {}
+        }
+    }
+    let rests: HashSet<parse::ParamId> = (tree.params().iter())
+        .filter_map(|param| match param.bind {
+            Bind::Record { rest, .. } => Some(rest),
+            _ => None,
+        })
+        .collect();
+    for (i, param) in tree.params().iter().enumerate() {
+        let id = parse::ParamId::from_usize(i).unwrap();
+        if let Bind::Record { .. } = param.bind {
+            if !rests.contains(&id) {
+                fold(param_range(tokens, tree, id));
+            }
+        }
+    }
+    // comments that start their own lines, on consecutive lines
+    let mut block: Option<(Range<usize>, u32)> = None;
+    for i in 0..tokens.len() {
+        let token = tokens.get(TokenId::from_usize(i).unwrap());
+        if token.kind != TokenKind::Comment {
+            continue;
+        }
+        let range = token.byte_range();
+        let line = source.lines.line_col(range.start.try_into().unwrap()).line;
+        let line_start = source.text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        if !source.text[line_start..range.start].trim().is_empty() {
+            continue;
+        }
+        block = match block {
+            Some((prev, end)) if end + 1 == line => Some((prev.start..range.end, line)),
+            prev => {
+                if let Some((range, _)) = prev {
+                    folds.push(Fold {
+                        range,
+                        comment: true,
+                    });
+                }
+                Some((range, line))
+            }
+        };
+    }
+    if let Some((range, _)) = block {
+        folds.push(Fold {
+            range,
+            comment: true,
+        });
+    }
+    Some(folds)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typecheck::typecheck;
+
+    use super::*;
+
+    fn graph(files: &[(&str, &str)]) -> Graph {
+        let mut graph = Graph::new(Uri::from_directory_path("/std").unwrap());
+        for &(path, text) in files {
+            let uri = Uri::from_file_path(path).unwrap();
+            graph.make_root(uri.clone());
+            graph.set_text(&uri, text.to_owned());
+        }
+        loop {
+            let analysis = graph.analysis();
+            if analysis.is_empty() {
+                break;
+            }
+            for job in analysis {
+                let (_, syn, deps) = &job;
+                let imports = deps.iter().map(|(_, dep)| dep.as_ref()).collect();
+                let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, imports);
+                graph.supply_semantic(job, Arc::new(module), errs);
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn test_items() {
+        let lib = "def sq(x: Float): Float = x * x\n";
+        let main = "import \"./lib\" use sq\n\ndef f[T](x: T): T = x\n\ndef g: Float = sq(2.0)\n";
+        let graph = graph(&[("/lib.adroit", lib), ("/main.adroit", main)]);
+        let items = items(&graph, &Uri::from_file_path("/main.adroit").unwrap()).unwrap();
+        let sq = Item {
+            name: "sq".to_owned(),
+            kind: Kind::Def,
+            detail: Some("Float -> Float".to_owned()),
+            range: 19..21,
+            selection: 19..21,
+            children: vec![],
+        };
+        assert_eq!(
+            items[0],
+            Item {
+                name: "./lib".to_owned(),
+                kind: Kind::Module,
+                detail: None,
+                range: 0..21,
+                selection: 7..14,
+                children: vec![sq],
+            },
+        );
+        let defs: Vec<_> = (items[1..].iter())
+            .map(|item| (&main[item.range.clone()], &main[item.selection.clone()]))
+            .collect();
+        assert_eq!(
+            defs,
+            [
+                ("def f[T](x: T): T = x", "f"),
+                ("def g: Float = sq(2.0)", "g"),
+            ],
+        );
+        let details: Vec<_> = items[1..]
+            .iter()
+            .map(|item| item.detail.as_deref())
+            .collect();
+        assert_eq!(details, [Some("T => T -> T"), Some("Float")]);
+    }
+
+    #[test]
+    fn test_workspace() {
+        let lib = "def sq(x: Float): Float = x * x\n\ndef sum(x: Float): Float = x\n";
+        let main = "def square: Int = 4\n";
+        let graph = graph(&[("/lib.adroit", lib), ("/main.adroit", main)]);
+        // whatever the standard library has depends on what the language server wrote to disk
+        let mut found: Vec<_> = (workspace(&graph, "SQ").into_iter())
+            .filter(|(uri, _, _)| !uri.as_str().starts_with(graph.stdlib().as_str()))
+            .map(|(uri, _, item)| (uri.to_file_path().unwrap(), item.name))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                ("/lib.adroit".into(), "sq".to_owned()),
+                ("/main.adroit".into(), "square".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_folds() {
+        let src =
+            "# one\n# two\ndef f({a}: Float): Float =\n  let b = a\n  let {c} = {c = b}\n  c\n";
+        let graph = graph(&[("/a.adroit", src)]);
+        let folds = folds(&graph, &Uri::from_file_path("/a.adroit").unwrap()).unwrap();
+        let folds: Vec<_> = (folds.into_iter())
+            .map(|fold| (&src[fold.range], fold.comment))
+            .collect();
+        assert_eq!(
+            folds,
+            [
+                (src[12..].trim_end(), false),
+                ("{c = b}", false),
+                // the result at the end stays visible
+                ("let b = a\n  let {c} = {c = b}", false),
+                ("{a}: Float", false),
+                ("{c}", false),
+                ("# one\n# two", true),
+            ],
+        );
+    }
+}
@@ -161,6 +161,9 @@ pub enum Expr {
     Undefined {
         token: TokenId,
     },
+    Hole {
+        token: TokenId,
+    },
     Unit {
         open: TokenId,
         close: TokenId,
@@ -649,6 +652,11 @@ impl Parser<'_> {
                 self.next();
                 Ok(self.tree.make_expr(Expr::Undefined { token }))
             }
+            Hole => {
+                let token = self.id;
+                self.next();
+                Ok(self.tree.make_expr(Expr::Hole { token }))
+            }
             Number => {
                 let val = self.id;
                 self.next();
@@ -656,7 +664,7 @@ impl Parser<'_> {
             }
             _ => Err(ParseError::Expected {
                 id: self.id,
-                kinds: LParen | LBrace | Ident | Undefined | Number,
+                kinds: LParen | LBrace | Ident | Undefined | Hole | Number,
             }),
         }
     }
@@ -675,7 +683,7 @@ impl Parser<'_> {
                     let after = self.get(self.after_close());
                     self.next();
                     // same set of tokens allowed at the start of an atomic expression
-                    if let LParen | LBrace | Ident | Undefined | Number = after {
+                    if let LParen | LBrace | Ident | Undefined | Hole | Number = after {
                         while self.peek() != RBracket {
                             let ty = self.ty()?;
                             expr = self.tree.make_expr(Expr::Inst { val: expr, ty });
@@ -711,7 +719,7 @@ impl Parser<'_> {
         // function application is the only place we forbid line breaks
         while !self.newline() {
             // same set of tokens allowed at the start of an atomic expression
-            if let LParen | LBrace | Ident | Undefined | Number = self.peek() {
+            if let LParen | LBrace | Ident | Undefined | Hole | Number = self.peek() {
                 let x = self.expr_access()?;
                 f = self.tree.make_expr(Expr::Apply { func: f, arg: x });
             } else {
@@ -161,6 +161,7 @@ impl Printer<'_> {
             }
             Expr::Name { name } => self.token(w, name)?,
             Expr::Undefined { token } => self.token(w, token)?,
+            Expr::Hole { token } => self.token(w, token)?,
             Expr::Unit { open, close } => {
                 self.token(w, open)?;
                 self.token(w, close)?;
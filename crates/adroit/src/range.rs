@@ -2,7 +2,7 @@ use std::{cell::Cell, cmp::Ordering, ops::Range};
 
 use crate::{
     lex::{TokenId, TokenKind, Tokens},
-    parse::{Bind, Expr, ExprId, Module, Param, ParamId, Type, TypeId},
+    parse::{Bind, DefId, Expr, ExprId, Import, Module, Param, ParamId, Type, TypeId},
     util::Id,
 };
 
@@ -258,6 +258,7 @@ impl<'a> Ranger<'a> {
             Expr::Paren { inner } => self.before(self.expr_start(inner)?),
             Expr::Name { name } => name,
             Expr::Undefined { token } => token,
+            Expr::Hole { token } => token,
             Expr::Unit { open, close: _ } => open,
             Expr::Number { val } => val,
             Expr::Pair { fst, snd: _ } => self.expr_start(fst)?,
@@ -307,6 +308,7 @@ impl<'a> Ranger<'a> {
             Expr::Paren { inner } => self.after(self.expr_end(inner)?),
             Expr::Name { name } => name,
             Expr::Undefined { token } => token,
+            Expr::Hole { token } => token,
             Expr::Unit { open: _, close } => close,
             Expr::Number { val } => val,
             Expr::Pair { fst: _, snd } => self.expr_end(snd)?,
@@ -369,6 +371,28 @@ pub fn expr_range(tokens: &Tokens, tree: &Module, id: ExprId) -> Option<Range<us
     Ranger::new(tokens, tree).expr_range(id)
 }
 
+/// The range of a definition, from its `def` keyword to the end of its body.
+pub fn def_range(tokens: &Tokens, tree: &Module, id: DefId) -> Option<Range<usize>> {
+    let ranger = Ranger::new(tokens, tree);
+    let def = tree.def(id);
+    Some(ranger.range(ranger.before(def.name), ranger.expr_end(def.body)?))
+}
+
+/// The range of an import, from its `import` keyword to the last name it uses.
+pub fn import_range(tokens: &Tokens, tree: &Module, import: &Import) -> Range<usize> {
+    let ranger = Ranger::new(tokens, tree);
+    let end = import.names.last().copied().unwrap_or(import.module);
+    ranger.range(ranger.before(import.module), end)
+}
+
+/// The ranges of all expressions in a module, indexed by their IDs.
+pub fn expr_ranges(tokens: &Tokens, tree: &Module) -> Vec<Option<Range<usize>>> {
+    let ranger = Ranger::with_cache(tokens, tree);
+    (0..tree.exprs().len())
+        .map(|i| ranger.expr_range(ExprId::from_usize(i).unwrap()))
+        .collect()
+}
+
 #[derive(Debug)]
 pub enum Node {
     Type(TypeId),
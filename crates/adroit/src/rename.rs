@@ -0,0 +1,311 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    graph::{Data, Graph, Uri},
+    lex::{lex, TokenId, TokenKind},
+    parse::{self, parse},
+    symbol::{self, Symbol},
+    typecheck::{self, typecheck, ImportId, TypeError},
+    util::Id,
+};
+
+/// A replacement of a byte range in a module's source text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// What a name refers to, in terms that don't change when other names are edited.
+#[derive(Debug, Eq, PartialEq)]
+enum Target {
+    Param(parse::ParamId),
+    Def(Option<ImportId>, parse::DefId),
+    TypeParam(parse::DefId, usize),
+    IndexVar(parse::ExprId),
+    Other,
+}
+
+fn type_var(tree: &parse::Module, def: TokenId) -> Target {
+    for (i, d) in tree.defs().iter().enumerate() {
+        if let Some(j) = d.types.iter().position(|&ty| ty == def) {
+            return Target::TypeParam(parse::DefId::from_usize(i).unwrap(), j);
+        }
+    }
+    for (i, expr) in tree.exprs().iter().enumerate() {
+        if let parse::Expr::Index { name, .. } = *expr {
+            if name == def {
+                return Target::IndexVar(parse::ExprId::from_usize(i).unwrap());
+            }
+        }
+    }
+    Target::Other
+}
+
+/// What every name expression and type name in a module refers to, in order.
+fn targets(tree: &parse::Module, module: &typecheck::Module) -> Vec<Target> {
+    let exprs = tree.exprs().iter().enumerate().filter_map(|(i, expr)| {
+        let parse::Expr::Name { .. } = expr else {
+            return None;
+        };
+        Some(match module.origin(parse::ExprId::from_usize(i).unwrap()) {
+            typecheck::Src::Param { id } => Target::Param(id),
+            typecheck::Src::Def { id } => Target::Def(None, id),
+            typecheck::Src::Import { src, id } => Target::Def(Some(src), id),
+            typecheck::Src::Expr { .. } | typecheck::Src::Inst { .. } => Target::Other,
+        })
+    });
+    let types = tree.types().iter().enumerate().filter_map(|(i, ty)| {
+        let parse::Type::Name { .. } = ty else {
+            return None;
+        };
+        let id = parse::TypeId::from_usize(i).unwrap();
+        Some(match module.ty(module.parsed_ty(id)) {
+            typecheck::Type::Var { src: None, def } => type_var(tree, def),
+            _ => Target::Other,
+        })
+    });
+    exprs.chain(types).collect()
+}
+
+/// Make edits to a source text, which must be sorted and not overlap.
+pub fn apply(text: &str, edits: &[Edit]) -> String {
+    let mut result = String::new();
+    let mut end = 0;
+    for edit in edits {
+        result.push_str(&text[end..edit.range.start]);
+        result.push_str(&edit.text);
+        end = edit.range.end;
+    }
+    result.push_str(&text[end..]);
+    result
+}
+
+/// Typecheck a module after renaming, or explain why the rename would break it.
+fn check(
+    name: &str,
+    text: &str,
+    imports: Vec<&typecheck::Module>,
+    before: &[Target],
+) -> Result<typecheck::Module, String> {
+    let tokens = lex(text).map_err(|_| format!("`{name}` is not a valid name"))?;
+    let tree = parse(&tokens).map_err(|_| format!("`{name}` is not a valid name"))?;
+    let (module, errs) = typecheck(text, &tokens, &tree, imports);
+    for err in &errs {
+        if let TypeError::Duplicate { name: id } = *err {
+            let duplicate = &text[tokens.get(id).byte_range()];
+            return Err(format!("`{duplicate}` is already defined"));
+        }
+    }
+    if !errs.is_empty() {
+        return Err(format!("renaming to `{name}` would cause a type error"));
+    }
+    if targets(&tree, &module) != before {
+        return Err(format!(
+            "renaming to `{name}` would change what another name refers to"
+        ));
+    }
+    Ok(module)
+}
+
+/// The symbol at an offset in an analyzed module, if it can be renamed.
+fn renamable(graph: &Graph, uri: &Uri, offset: usize) -> Result<(Symbol, Range<usize>), String> {
+    let (symbol, range) = symbol::resolve(graph, uri, |resolver| resolver.find(offset))
+        .flatten()
+        .ok_or("no symbol to rename here")?;
+    match symbol {
+        Symbol::Module { .. } => return Err("modules can't be renamed".to_owned()),
+        Symbol::TypeVar { src: Some(_), .. } => {
+            return Err("type parameters can only be renamed where they are declared".to_owned())
+        }
+        _ => {}
+    }
+    let occurrences = symbol::occurrences(graph, uri, symbol);
+    let (home, _) = &occurrences[0];
+    // a dependent that failed to parse doesn't show up as an occurrence, but might use the symbol
+    let dependents = match symbol {
+        Symbol::Def { .. } => graph.get(home).dependents.iter().collect(),
+        _ => vec![],
+    };
+    for module in occurrences
+        .iter()
+        .map(|(module, _)| module)
+        .chain(dependents)
+    {
+        if module.as_str().starts_with(graph.stdlib().as_str()) {
+            return Err("definitions in the standard library can't be renamed".to_owned());
+        }
+        match &graph.get(module).data {
+            // names after the first type error aren't resolved, so we couldn't check for capture
+            Data::Analyzed { errs, .. } if errs.is_empty() => {}
+            _ => return Err(format!("`{}` has errors", module.as_str())),
+        }
+    }
+    Ok((symbol, range))
+}
+
+/// The range of the name at an offset, if it refers to something that can be renamed.
+pub fn prepare_rename(graph: &Graph, uri: &Uri, offset: usize) -> Result<Range<usize>, String> {
+    renamable(graph, uri, offset).map(|(_, range)| range)
+}
+
+/// The edits to every affected module that rename the symbol at an offset.
+pub fn rename(
+    graph: &Graph,
+    uri: &Uri,
+    offset: usize,
+    name: &str,
+) -> Result<Vec<(Uri, Vec<Edit>)>, String> {
+    let (symbol, _) = renamable(graph, uri, offset)?;
+    let valid = match lex(name) {
+        Ok(tokens) => {
+            let token = tokens.get(TokenId { index: 0 });
+            tokens.len() == 2
+                && token.kind == TokenKind::Ident
+                && token.byte_range() == (0..name.len())
+        }
+        Err(_) => false,
+    };
+    if !valid {
+        return Err(format!("`{name}` is not a valid name"));
+    }
+    let field = matches!(symbol, Symbol::Field { .. });
+    let mut changes = vec![];
+    for (module, symbol) in symbol::occurrences(graph, uri, symbol) {
+        let edits = symbol::resolve(graph, &module, |resolver| {
+            let mut ranges = resolver.uses(symbol);
+            if let Some((path, range)) = resolver.declaration(symbol) {
+                if path == module.as_str() {
+                    ranges.push(range);
+                }
+            }
+            ranges.sort_by_key(|range| range.start);
+            ranges.dedup();
+            let shorthands = resolver.shorthands();
+            (ranges.into_iter())
+                .map(|range| {
+                    let text = if !shorthands.contains(&range) {
+                        name.to_owned()
+                    } else if field {
+                        format!("{name} = {}", resolver.text(range.clone()))
+                    } else {
+                        format!("{} = {name}", resolver.text(range.clone()))
+                    };
+                    Edit { range, text }
+                })
+                .collect::<Vec<_>>()
+        })
+        .ok_or_else(|| format!("`{}` has errors", module.as_str()))?;
+        changes.push((module, edits));
+    }
+    // the first module is the one the others import from, so check it first
+    let mut renamed: HashMap<&Uri, typecheck::Module> = HashMap::new();
+    for (module, edits) in &changes {
+        let Data::Analyzed { syn, sem, .. } = &graph.get(module).data else {
+            unreachable!();
+        };
+        let uris = graph.imports(module).map_err(|()| "cyclic import")?;
+        let imports = (uris.iter())
+            .map(|import| match renamed.get(import) {
+                Some(module) => module,
+                None => match &graph.get(import).data {
+                    Data::Analyzed { sem, .. } => sem.as_ref(),
+                    _ => unreachable!(),
+                },
+            })
+            .collect();
+        let text = apply(&syn.src.text, edits);
+        let before = targets(&syn.tree, sem);
+        let checked = check(name, &text, imports, &before)?;
+        renamed.insert(module, checked);
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyzed(modules: &[(&str, &str)]) -> Graph {
+        let stdlib = Uri::from_directory_path("/std").unwrap();
+        let mut graph = Graph::new(stdlib);
+        let (root, _) = modules[0];
+        graph.make_root(Uri::from_file_path(root).unwrap());
+        loop {
+            let pending = graph.pending();
+            if pending.is_empty() {
+                break;
+            }
+            for uri in pending {
+                let path = uri.to_file_path().unwrap();
+                let (_, text) = modules.iter().find(|(p, _)| path.ends_with(p)).unwrap();
+                graph.set_text(&uri, text.to_string());
+            }
+        }
+        loop {
+            let analysis = graph.analysis();
+            if analysis.is_empty() {
+                break;
+            }
+            for job in analysis {
+                let (_, syn, deps) = &job;
+                let imports = deps.iter().map(|(_, dep)| dep.as_ref()).collect();
+                let (module, errs) = typecheck(&syn.src.text, &syn.toks, &syn.tree, imports);
+                graph.supply_semantic(job, module.into(), errs);
+            }
+        }
+        graph
+    }
+
+    fn renamed(modules: &[(&str, &str)], offset: usize, name: &str) -> Result<Vec<String>, String> {
+        let graph = analyzed(modules);
+        let root = Uri::from_file_path(modules[0].0).unwrap();
+        let changes = rename(&graph, &root, offset, name)?;
+        Ok(modules
+            .iter()
+            .map(|(path, text)| {
+                let uri = Uri::from_file_path(path).unwrap();
+                match changes.iter().find(|(module, _)| *module == uri) {
+                    Some((_, edits)) => apply(text, edits),
+                    None => text.to_string(),
+                }
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_rename_shorthand() {
+        let src = "def f(x: Int): Int =\n  let {a} = {a = x}\n  a\n";
+        let field = renamed(&[("/a.adroit", src)], 34, "b").unwrap();
+        assert_eq!(
+            field,
+            ["def f(x: Int): Int =\n  let {b = a} = {b = x}\n  a\n"]
+        );
+        let var = renamed(&[("/a.adroit", src)], 43, "b").unwrap();
+        assert_eq!(
+            var,
+            ["def f(x: Int): Int =\n  let {a = b} = {a = x}\n  b\n"]
+        );
+    }
+
+    #[test]
+    fn test_rename_import() {
+        let a = "import \"./lib\" use twice\n\ndef four: Int = twice(2)\n";
+        let lib = "def twice(x: Int): Int = x + x\n";
+        let modules = [("/a.adroit", a), ("/lib.adroit", lib)];
+        let expected = [
+            "import \"./lib\" use double\n\ndef four: Int = double(2)\n",
+            "def double(x: Int): Int = x + x\n",
+        ];
+        assert_eq!(renamed(&modules, 44, "double").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_rename_conflict() {
+        let src = "def f(x: Int): Int =\n  let y = x\n  x + y\n";
+        let modules = [("/a.adroit", src)];
+        assert!(renamed(&modules, 27, "x").is_err());
+        assert!(renamed(&modules, 27, "f").is_ok());
+        assert!(renamed(&modules, 27, "def").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    compile::{FullModule, GraphImporter, Importer, Printer},
+    graph::{Data, Graph, Uri},
+    lex::lex,
+    parse::{self, parse, Bind, Expr},
+    range::{bind_range, expr_ranges},
+    typecheck::{self, typecheck, Src},
+    util::Id,
+};
+
+/// The signature of a function being applied, as shown while writing its argument.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signature {
+    pub label: String,
+
+    /// The byte range of each parameter in the label.
+    pub params: Vec<Range<usize>>,
+
+    /// The index of the parameter being written.
+    pub active: usize,
+}
+
+/// The tuple components of an expression, which are right-nested pairs.
+fn components(tree: &parse::Module, mut id: parse::ExprId) -> Vec<parse::ExprId> {
+    let mut components = vec![];
+    while let Expr::Pair { fst, snd } = tree.expr(id) {
+        components.push(fst);
+        id = snd;
+    }
+    components.push(id);
+    components
+}
+
+/// The tuple components of a parameter, which are right-nested pairs.
+fn param_components(tree: &parse::Module, mut id: parse::ParamId) -> Vec<parse::ParamId> {
+    let mut components = vec![];
+    while let Bind::Pair { fst, snd } = tree.param(id).bind {
+        components.push(fst);
+        id = snd;
+    }
+    components.push(id);
+    components
+}
+
+/// Builds up a signature label, keeping track of where the parameters are.
+#[derive(Debug, Default)]
+struct Label {
+    text: String,
+    params: Vec<Range<usize>>,
+}
+
+impl Label {
+    fn push(&mut self, s: &str) {
+        self.text.push_str(s);
+    }
+
+    fn param(&mut self, s: &str) {
+        let start = self.text.len();
+        self.text.push_str(s);
+        self.params.push(start..self.text.len());
+    }
+
+    fn types(&mut self, names: &[String]) {
+        if !names.is_empty() {
+            self.push(&format!("[{}]", names.join(", ")));
+        }
+    }
+}
+
+/// The signature of a function with the given type, in groups of curried parameters.
+fn from_type<'a>(
+    printer: &Printer<'a, GraphImporter<'a>>,
+    module: &typecheck::Module,
+    label: &mut Label,
+    mut ty: typecheck::TypeId,
+) -> Vec<usize> {
+    use typecheck::Type::*;
+    let mut groups = vec![];
+    label.push(": ");
+    while let Func { dom, cod } = module.ty(ty) {
+        let mut components = vec![];
+        let mut t = dom;
+        while let Prod { fst, snd } = module.ty(t) {
+            components.push(fst);
+            t = snd;
+        }
+        components.push(t);
+        groups.push(components.len());
+        for (i, component) in components.into_iter().enumerate() {
+            if i > 0 {
+                label.push(" * ");
+            }
+            let text = match module.ty(component) {
+                Prod { .. } | Sum { .. } | Func { .. } => format!("({})", printer.ty(component)),
+                _ => printer.ty(component).to_string(),
+            };
+            label.param(&text);
+        }
+        label.push(" -> ");
+        ty = cod;
+    }
+    label.push(&printer.ty(ty).to_string());
+    groups
+}
+
+/// The signature of a top-level definition, in groups of curried parameters.
+fn from_def<'a>(
+    full: &FullModule<'a>,
+    printer: &Printer<'a, GraphImporter<'a>>,
+    label: &mut Label,
+    id: parse::DefId,
+) -> Vec<usize> {
+    let module = &full.module;
+    let def = full.tree.def(id);
+    let text = |range: Range<usize>| &full.source[range];
+    label.push(text(full.tokens.get(def.name).byte_range()));
+    let types: Vec<String> = (def.types.iter())
+        .map(|&t| text(full.tokens.get(t).byte_range()).to_owned())
+        .collect();
+    label.types(&types);
+    let mut ty = module.val(module.def(id)).ty;
+    while let typecheck::Type::Poly { var: _, inner } = module.ty(ty) {
+        ty = inner;
+    }
+    if def.params.is_empty() {
+        return from_type(printer, module, label, ty);
+    }
+    let mut groups = vec![];
+    for &param in &def.params {
+        let components = param_components(full.tree, param);
+        groups.push(components.len());
+        label.push("(");
+        for (i, component) in components.into_iter().enumerate() {
+            if i > 0 {
+                label.push(", ");
+            }
+            let bind = text(bind_range(full.tokens, full.tree, component).unwrap());
+            let ty = printer.ty(module.val(module.param(component)).ty);
+            label.param(&format!("{bind}: {ty}"));
+        }
+        label.push(")");
+        if let typecheck::Type::Func { dom: _, cod } = module.ty(ty) {
+            ty = cod;
+        }
+    }
+    label.push(&format!(": {}", printer.ty(ty)));
+    groups
+}
+
+/// The signature of a top-level definition, without the `def` keyword.
+pub fn def_label<'a>(
+    full: &FullModule<'a>,
+    printer: &Printer<'a, GraphImporter<'a>>,
+    id: parse::DefId,
+) -> String {
+    let mut label = Label::default();
+    from_def(full, printer, &mut label, id);
+    label.text
+}
+
+/// The signature of the innermost application whose argument contains the given byte offset.
+fn help(
+    graph: &Graph,
+    full: FullModule,
+    deps: &[(Uri, Arc<typecheck::Module>)],
+    offset: usize,
+) -> Option<Signature> {
+    let tree = full.tree;
+    let ranges = expr_ranges(full.tokens, tree);
+    let range = |id: parse::ExprId| ranges[id.to_usize()].clone();
+    let (func, arg) = (tree.exprs().iter())
+        .filter_map(|expr| match *expr {
+            Expr::Apply { func, arg } | Expr::Map { func, arg } => {
+                let r = range(arg)?;
+                (r.start <= offset && offset <= r.end).then_some((func, arg, r))
+            }
+            _ => None,
+        })
+        .min_by_key(|(_, _, r)| r.len())
+        .map(|(func, arg, _)| (func, arg))?;
+    // earlier arguments of a curried function are applications too
+    let mut group = 0;
+    let mut head = func;
+    loop {
+        match tree.expr(head) {
+            Expr::Apply { func, arg: _ } => {
+                group += 1;
+                head = func;
+            }
+            Expr::Inst { val, ty: _ } => head = val,
+            Expr::Paren { inner } => head = inner,
+            _ => break,
+        }
+    }
+    let Expr::Name { name } = tree.expr(head) else {
+        return None;
+    };
+    let inner = match tree.expr(arg) {
+        Expr::Paren { inner } => inner,
+        _ => arg,
+    };
+    let components = components(tree, inner);
+    let component = (components.iter())
+        .position(|&c| range(c).is_some_and(|r| offset <= r.end))
+        .unwrap_or(components.len() - 1);
+    let mut label = Label::default();
+    let importer = GraphImporter { graph, deps };
+    let groups = match full.module.origin(head) {
+        Src::Def { id } => {
+            let printer = Printer::new(full.clone(), importer);
+            from_def(&full, &printer, &mut label, id)
+        }
+        Src::Import { src, id } => {
+            let import = importer.import(src)?;
+            let Data::Analyzed { deps, .. } = &graph.get(&deps[src.to_usize()].0).data else {
+                return None;
+            };
+            let importer = GraphImporter { graph, deps };
+            let printer = Printer::new(import.clone(), importer);
+            from_def(&import, &printer, &mut label, id)
+        }
+        _ => {
+            let printer = Printer::new(full.clone(), importer);
+            label.push(&full.source[full.tokens.get(name).byte_range()]);
+            let mut ty = full.module.val(full.module.expr(head)).ty;
+            let mut types = vec![];
+            while let typecheck::Type::Poly { var, inner } = full.module.ty(ty) {
+                types.push(printer.ty(var).to_string());
+                ty = inner;
+            }
+            label.types(&types);
+            from_type(&printer, &full.module, &mut label, ty)
+        }
+    };
+    if group >= groups.len() || component >= groups[group] {
+        return None;
+    }
+    Some(Signature {
+        label: label.text,
+        params: label.params,
+        active: groups[..group].iter().sum::<usize>() + component,
+    })
+}
+
+/// The signature of the function being applied at the given byte offset in a module.
+///
+/// If the module doesn't parse, a placeholder is inserted at the cursor before parsing it again,
+/// since the argument being written is usually what's missing.
+pub fn signature_help(graph: &Graph, uri: &Uri, offset: usize) -> Option<Signature> {
+    let (text, placeholder) = match &graph.get(uri).data {
+        Data::Analyzed { syn, sem, deps, .. } => {
+            let full = FullModule {
+                source: &syn.src.text,
+                tokens: &syn.toks,
+                tree: &syn.tree,
+                module: Arc::clone(sem),
+            };
+            return help(graph, full, deps, offset);
+        }
+        Data::Pending | Data::Read { .. } => return None,
+        Data::Lexed { src, .. } => (&src.text, true),
+        // waiting on its imports, some of which might already be analyzed
+        Data::Parsed { syn } => (&syn.src.text, false),
+    };
+    if !text.is_char_boundary(offset) {
+        return None;
+    }
+    let mut source = text.clone();
+    if placeholder {
+        source.insert_str(offset, " undefined ");
+    }
+    let tokens = lex(&source).ok()?;
+    let tree = parse(&tokens).ok()?;
+    let uris = (tree.imports().iter())
+        .map(|import| uri.resolve(graph.stdlib(), &tokens.get(import.module).string(&source)))
+        .collect::<Result<Vec<Uri>, ()>>()
+        .ok()?;
+    let deps = (uris.into_iter())
+        .map(|import| match &graph.get(&import).data {
+            Data::Analyzed { sem, errs, .. } if errs.is_empty() => Some((import, Arc::clone(sem))),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let imports = deps.iter().map(|(_, sem)| sem.as_ref()).collect();
+    let (module, _) = typecheck(&source, &tokens, &tree, imports);
+    let full = FullModule {
+        source: &source,
+        tokens: &tokens,
+        tree: &tree,
+        module: Arc::new(module),
+    };
+    let offset = if placeholder { offset + 1 } else { offset };
+    help(graph, full, &deps, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn help(src: &str, cursor: &str) -> Option<(String, Vec<String>, usize)> {
+        let uri = Uri::from_file_path("/a.adroit").unwrap();
+        let mut graph = Graph::new(Uri::from_directory_path("/std").unwrap());
+        graph.make_root(uri.clone());
+        graph.set_text(&uri, src.to_owned());
+        let offset = src.find(cursor).unwrap() + cursor.len();
+        let sig = signature_help(&graph, &uri, offset)?;
+        let params = (sig.params.iter())
+            .map(|range| sig.label[range.clone()].to_owned())
+            .collect();
+        Some((sig.label, params, sig.active))
+    }
+
+    #[test]
+    fn test_signature_help() {
+        let src = "def f[T](x: T, y: Int)(z: Float): T = x\n\ndef g: Int = f(1, )(2.0)\n";
+        let (label, params, active) = help(src, "f(1, ").unwrap();
+        assert_eq!(label, "f[T](x: T, y: Int)(z: Float): T");
+        assert_eq!(params, ["x: T", "y: Int", "z: Float"]);
+        assert_eq!(active, 1);
+        let src = "def f[T](x: T, y: Int)(z: Float): T = x\n\ndef g: Int = f(1, 2)(3.0)\n";
+        assert_eq!(help(src, ")(3").unwrap().2, 2);
+        assert!(help(src, "def g").is_none());
+    }
+}
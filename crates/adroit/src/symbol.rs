@@ -0,0 +1,330 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    compile::{FullModule, GraphImporter, Importer},
+    graph::{Data, Graph, Uri},
+    lex::TokenId,
+    parse::{self, Bind},
+    range::{self, bind_range},
+    typecheck::{self, FieldId, ImportId},
+    util::Id,
+};
+
+/// Something that a name can refer to, identified by where it is declared.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Symbol {
+    /// A local variable or function parameter in this module.
+    Param { id: parse::ParamId },
+
+    /// A top-level definition, either in this module or in an imported one.
+    Def {
+        src: Option<ImportId>,
+        id: parse::DefId,
+    },
+
+    /// A type parameter, identified by the token that declares it.
+    TypeVar { src: Option<ImportId>, def: TokenId },
+
+    /// An imported module as a whole.
+    Module { src: ImportId },
+
+    /// A record field name, which has no single declaration.
+    Field { id: FieldId },
+}
+
+#[derive(Clone, Debug)]
+pub struct Resolver<'a, I> {
+    path: &'a str,
+    full: FullModule<'a>,
+    import: I,
+}
+
+impl<'a, I: Importer<'a>> Resolver<'a, I> {
+    pub fn new(path: &'a str, full: FullModule<'a>, import: I) -> Self {
+        Self { path, full, import }
+    }
+
+    fn token_range(&self, id: TokenId) -> Range<usize> {
+        self.full.tokens.get(id).byte_range()
+    }
+
+    fn src(&self, src: typecheck::Src) -> Option<Symbol> {
+        match src {
+            typecheck::Src::Param { id } => Some(Symbol::Param { id }),
+            typecheck::Src::Def { id } => Some(Symbol::Def { src: None, id }),
+            typecheck::Src::Import { src, id } => Some(Symbol::Def { src: Some(src), id }),
+            typecheck::Src::Expr { .. } | typecheck::Src::Inst { .. } => None,
+        }
+    }
+
+    /// The symbol declared or referred to by a token that isn't part of any type, param or expr.
+    fn token(&self, offset: usize) -> Option<(Symbol, TokenId)> {
+        let tree = self.full.tree;
+        let hit = |id: TokenId| self.token_range(id).contains(&offset);
+        for (i, import) in tree.imports().iter().enumerate() {
+            let src = ImportId::from_usize(i)?;
+            if hit(import.module) {
+                return Some((Symbol::Module { src }, import.module));
+            }
+            if let Some(&name) = import.names.iter().find(|&&name| hit(name)) {
+                let full = self.import.import(src)?;
+                let id = full
+                    .module
+                    .export(&self.full.source[self.token_range(name)])?;
+                return Some((Symbol::Def { src: Some(src), id }, name));
+            }
+        }
+        for (i, def) in tree.defs().iter().enumerate() {
+            if hit(def.name) {
+                let id = parse::DefId::from_usize(i)?;
+                return Some((Symbol::Def { src: None, id }, def.name));
+            }
+            if let Some(&def) = def.types.iter().find(|&&ty| hit(ty)) {
+                return Some((Symbol::TypeVar { src: None, def }, def));
+            }
+        }
+        for expr in tree.exprs() {
+            match *expr {
+                parse::Expr::Index { name, .. } if hit(name) => {
+                    return Some((
+                        Symbol::TypeVar {
+                            src: None,
+                            def: name,
+                        },
+                        name,
+                    ));
+                }
+                parse::Expr::Record { name, .. }
+                    if hit(name) && !self.shorthands().contains(&self.token_range(name)) =>
+                {
+                    return Some((self.field(name)?, name));
+                }
+                _ => {}
+            }
+        }
+        tree.params().iter().find_map(|param| match param.bind {
+            Bind::Record { name, .. }
+                if hit(name) && !self.shorthands().contains(&self.token_range(name)) =>
+            {
+                Some((self.field(name)?, name))
+            }
+            _ => None,
+        })
+    }
+
+    fn field(&self, name: TokenId) -> Option<Symbol> {
+        let id = self
+            .full
+            .module
+            .field_id(&self.full.source[self.token_range(name)])?;
+        Some(Symbol::Field { id })
+    }
+
+    pub fn text(&self, range: Range<usize>) -> &'a str {
+        &self.full.source[range]
+    }
+
+    /// The ranges of tokens that are both a record field name and a variable, as in `{x}`.
+    pub fn shorthands(&self) -> Vec<Range<usize>> {
+        let tree = self.full.tree;
+        let exprs = tree.exprs().iter().filter_map(|expr| match *expr {
+            parse::Expr::Record { name, field, .. } => match tree.expr(field) {
+                parse::Expr::Name { name: var } if var == name => Some(name),
+                _ => None,
+            },
+            _ => None,
+        });
+        let binds = tree.params().iter().filter_map(|param| match param.bind {
+            Bind::Record { name, field, .. } => match tree.param(field).bind {
+                Bind::Name { name: var } if var == name => Some(name),
+                _ => None,
+            },
+            _ => None,
+        });
+        exprs
+            .chain(binds)
+            .map(|name| self.token_range(name))
+            .collect()
+    }
+
+    /// Whether a symbol can be looked up, which isn't the case for one from an import that has
+    /// changed since this module was typechecked.
+    fn available(&self, symbol: Symbol) -> bool {
+        match symbol {
+            Symbol::Def { src: Some(src), .. } | Symbol::TypeVar { src: Some(src), .. } => {
+                self.import.import(src).is_some()
+            }
+            _ => true,
+        }
+    }
+
+    /// The symbol referred to at the given byte offset, along with the range of its name there.
+    pub fn find(&self, offset: usize) -> Option<(Symbol, Range<usize>)> {
+        if let Some((symbol, token)) = self.token(offset) {
+            return Some((symbol, self.token_range(token)));
+        }
+        let (node, bytes) = range::find(self.full.tokens, self.full.tree, offset)?;
+        let module = &self.full.module;
+        let symbol = match node {
+            range::Node::Type(id) => match self.full.tree.ty(id) {
+                parse::Type::Name { name: _ } => match module.ty(module.parsed_ty(id)) {
+                    typecheck::Type::Var { src, def } => Some(Symbol::TypeVar { src, def }),
+                    _ => None,
+                },
+                _ => None,
+            },
+            range::Node::Param(id) => match self.full.tree.param(id).bind {
+                Bind::Name { name: _ } => Some(Symbol::Param { id }),
+                _ => None,
+            },
+            range::Node::Expr(id) => match self.full.tree.expr(id) {
+                parse::Expr::Name { name: _ } => self.src(module.origin(id)),
+                _ => None,
+            },
+        }
+        .filter(|&symbol| self.available(symbol))?;
+        Some((symbol, bytes))
+    }
+
+    /// The ranges of all names in this module that refer to a symbol, other than its declaration.
+    pub fn uses(&self, symbol: Symbol) -> Vec<Range<usize>> {
+        let tree = self.full.tree;
+        let module = &self.full.module;
+        let mut ranges = vec![];
+        if let Symbol::Def { src: Some(src), id } = symbol {
+            // definitions from an outdated import can't be matched up with the ones used here
+            let Some(full) = self.import.import(src) else {
+                return vec![];
+            };
+            for &name in &tree.imports()[src.to_usize()].names {
+                let range = self.token_range(name);
+                if full.module.export(&self.full.source[range.clone()]) == Some(id) {
+                    ranges.push(range);
+                }
+            }
+        }
+        for (i, expr) in tree.exprs().iter().enumerate() {
+            if let parse::Expr::Name { name } = *expr {
+                let id = parse::ExprId::from_usize(i).unwrap();
+                if self.src(module.origin(id)) == Some(symbol) {
+                    ranges.push(self.token_range(name));
+                }
+            }
+        }
+        if let Symbol::Field { id } = symbol {
+            let field = self.full.module.field(id);
+            let names = tree.exprs().iter().filter_map(|expr| match *expr {
+                parse::Expr::Record { name, .. } => Some(name),
+                _ => None,
+            });
+            let binds = tree.params().iter().filter_map(|param| match param.bind {
+                Bind::Record { name, .. } => Some(name),
+                _ => None,
+            });
+            for name in names.chain(binds) {
+                let range = self.token_range(name);
+                if self.full.source[range.clone()] == *field {
+                    ranges.push(range);
+                }
+            }
+        }
+        if let Symbol::TypeVar { .. } = symbol {
+            for (i, ty) in tree.types().iter().enumerate() {
+                if let parse::Type::Name { name } = *ty {
+                    let id = parse::TypeId::from_usize(i).unwrap();
+                    if let typecheck::Type::Var { src, def } = module.ty(module.parsed_ty(id)) {
+                        if (Symbol::TypeVar { src, def }) == symbol {
+                            ranges.push(self.token_range(name));
+                        }
+                    }
+                }
+            }
+        }
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
+
+    /// The span of the name that declares a symbol, if it has one.
+    pub fn declaration(&self, symbol: Symbol) -> Option<(&'a str, Range<usize>)> {
+        Some(match symbol {
+            Symbol::Param { id } => (
+                self.path,
+                bind_range(self.full.tokens, self.full.tree, id).unwrap(),
+            ),
+            Symbol::Def { src: None, id } => {
+                (self.path, self.token_range(self.full.tree.def(id).name))
+            }
+            Symbol::Def { src: Some(src), id } => {
+                let full = self.import.import(src)?;
+                let range = full.tokens.get(full.tree.def(id).name).byte_range();
+                (self.import.path(src), range)
+            }
+            Symbol::TypeVar { src: None, def } => (self.path, self.token_range(def)),
+            Symbol::TypeVar {
+                src: Some(src),
+                def,
+            } => {
+                let full = self.import.import(src)?;
+                (self.import.path(src), full.tokens.get(def).byte_range())
+            }
+            Symbol::Module { src } => (self.import.path(src), 0..0),
+            Symbol::Field { id: _ } => return None,
+        })
+    }
+}
+
+/// Call a function with a resolver for a module in the graph, if that module has been analyzed.
+pub fn resolve<T>(
+    graph: &Graph,
+    uri: &Uri,
+    f: impl for<'b> FnOnce(&Resolver<'b, GraphImporter<'b>>) -> T,
+) -> Option<T> {
+    let (syn, sem, deps) = match &graph.get(uri).data {
+        Data::Analyzed { syn, sem, deps, .. } => (syn, sem, deps),
+        _ => return None,
+    };
+    let full = FullModule {
+        source: &syn.src.text,
+        tokens: &syn.toks,
+        tree: &syn.tree,
+        module: Arc::clone(sem),
+    };
+    let importer = GraphImporter { graph, deps };
+    Some(f(&Resolver::new(uri.as_str(), full, importer)))
+}
+
+/// Every module that can refer to a symbol from the given module, along with how it does so,
+/// starting with the module that declares it.
+pub fn occurrences(graph: &Graph, uri: &Uri, symbol: Symbol) -> Vec<(Uri, Symbol)> {
+    let (home, id) = match symbol {
+        Symbol::Def { src: None, id } => (uri.clone(), id),
+        Symbol::Def { src: Some(src), id } => match graph.imports(uri) {
+            Ok(imports) => (imports[src.to_usize()].clone(), id),
+            Err(()) => return vec![],
+        },
+        _ => return vec![(uri.clone(), symbol)],
+    };
+    let mut occurrences = vec![(home.clone(), Symbol::Def { src: None, id })];
+    // definitions can't be re-exported, so only direct dependents can use them
+    for dependent in &graph.get(&home).dependents {
+        if let Ok(imports) = graph.imports(dependent) {
+            for (i, import) in imports.iter().enumerate() {
+                if let (true, Some(src)) = (*import == home, ImportId::from_usize(i)) {
+                    let symbol = Symbol::Def { src: Some(src), id };
+                    occurrences.push((dependent.clone(), symbol));
+                }
+            }
+        }
+    }
+    occurrences
+}
+
+/// Every use of a symbol, in the given module and in every other module that can refer to it.
+pub fn references(graph: &Graph, uri: &Uri, symbol: Symbol) -> Vec<(Uri, Range<usize>)> {
+    let mut references = vec![];
+    for (module, symbol) in occurrences(graph, uri, symbol) {
+        let uses = resolve(graph, &module, |resolver| resolver.uses(symbol)).unwrap_or_default();
+        references.extend(uses.into_iter().map(|range| (module.clone(), range)));
+    }
+    references
+}
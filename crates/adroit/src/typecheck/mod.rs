@@ -1,4 +1,7 @@
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem::take,
+};
 
 use indexmap::{map::RawEntryApiV1, IndexMap};
 use serde::{ser::SerializeSeq, Serialize, Serializer};
@@ -117,6 +120,14 @@ pub enum Type {
         id: UnknownId,
         scalar: TypeId,
     },
+    /// The type of an integer literal, defaulting to `Int` if nothing else determines it.
+    IntLit {
+        id: UnknownId,
+    },
+    /// The type of a fractional literal, defaulting to `Float` if nothing else determines it.
+    FloatLit {
+        id: UnknownId,
+    },
     Fragment,
     Var {
         src: Option<ImportId>,
@@ -127,8 +138,12 @@ pub enum Type {
         inner: TypeId,
     },
     Unit,
+    /// Also written `Int64`.
     Int,
+    Int32,
+    /// Also written `Float64`.
     Float,
+    Float32,
     Prod {
         fst: TypeId,
         snd: TypeId,
@@ -172,12 +187,14 @@ pub struct Val {
 #[derive(Debug)]
 struct Fields {
     fields: IndexMap<String, ()>,
+    limit: usize,
 }
 
 impl Fields {
-    fn new() -> Self {
+    fn new(limit: usize) -> Self {
         Self {
             fields: IndexMap::new(),
+            limit,
         }
     }
 
@@ -186,10 +203,19 @@ impl Fields {
         s
     }
 
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.fields.keys().map(|s| s.as_str())
+    }
+
+    fn find(&self, field: &str) -> Option<FieldId> {
+        FieldId::from_usize(self.fields.get_index_of(field)?)
+    }
+
     fn make(&mut self, field: &str) -> TypeResult<FieldId> {
         let entry = self.fields.raw_entry_mut_v1().from_key(field);
         // maybe more fields than tokens, because of imports
-        let id = FieldId::from_usize(entry.index()).ok_or(TypeError::TooManyFields)?;
+        let id =
+            capped(entry.index(), self.limit).ok_or(TypeError::TooManyFields { site: None })?;
         entry.or_insert_with(|| (field.to_owned(), ()));
         Ok(id)
     }
@@ -205,23 +231,69 @@ impl Serialize for Fields {
     }
 }
 
+/// How many of each kind of ID a module can use before typechecking it gives up.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub fields: usize,
+    pub types: usize,
+    pub imports: usize,
+}
+
+impl Limits {
+    /// As many as the IDs themselves can count.
+    pub const MAX: Self = Self {
+        fields: usize::MAX,
+        types: usize::MAX,
+        imports: usize::MAX,
+    };
+}
+
+/// Like `Id::from_usize`, for the IDs that a module can run out of, which happens once `n` reaches
+/// `limit` if that comes first.
+fn capped<I: Id>(n: usize, limit: usize) -> Option<I> {
+    if n < limit {
+        I::from_usize(n)
+    } else {
+        None
+    }
+}
+
 #[derive(Debug)]
 enum BasicError {
     TooManyTypes,
     FailedToUnify,
+    Size { left: TypeId, right: TypeId },
 }
 
-#[derive(Debug)]
+/// An index type with its grouping and any `()` factors forgotten, since neither changes how many
+/// elements there are or what order they come in.
+///
+/// Operands stay in order: an index of `M * N` is a pair whose first part indexes `M`, so it can't
+/// stand in for an index of `N * M`.
+#[derive(Clone, Debug)]
+enum Shape {
+    Atom(TypeId),
+
+    /// At least two terms, none of which are sums themselves.
+    Sum(Vec<Shape>),
+
+    /// Factors other than `()`, none of which are products themselves; with none at all, it's `()`.
+    Prod(Vec<Shape>),
+}
+
+#[derive(Clone, Debug)]
 struct Types {
     unknowns: usize,
     types: IndexMap<Type, TypeId>,
+    limit: usize,
 }
 
 impl Types {
-    fn new() -> Self {
+    fn new(limit: usize) -> Self {
         Self {
             unknowns: 0,
             types: IndexMap::new(),
+            limit,
         }
     }
 
@@ -233,7 +305,7 @@ impl Types {
     fn make(&mut self, ty: Type) -> Result<TypeId, BasicError> {
         let entry = self.types.entry(ty);
         // maybe more types than tokens, because of imports
-        let parent = TypeId::from_usize(entry.index()).ok_or(BasicError::TooManyTypes)?;
+        let parent = capped(entry.index(), self.limit).ok_or(BasicError::TooManyTypes)?;
         entry.or_insert(parent);
         Ok(parent)
     }
@@ -265,6 +337,109 @@ impl Types {
         element
     }
 
+    fn shape(&mut self, t: TypeId) -> Shape {
+        let t = self.root(t);
+        match self.get(t) {
+            Type::Unit => Shape::Prod(vec![]),
+            Type::Sum { left, right } => {
+                let mut terms = vec![];
+                for side in [left, right] {
+                    match self.shape(side) {
+                        Shape::Sum(inner) => terms.extend(inner),
+                        term => terms.push(term),
+                    }
+                }
+                Shape::Sum(terms)
+            }
+            Type::Prod { fst, snd } => {
+                let mut factors = vec![];
+                for side in [fst, snd] {
+                    match self.shape(side) {
+                        Shape::Prod(inner) => factors.extend(inner),
+                        factor => factors.push(factor),
+                    }
+                }
+                match factors.len() {
+                    1 => factors.pop().unwrap(),
+                    _ => Shape::Prod(factors),
+                }
+            }
+            _ => Shape::Atom(t),
+        }
+    }
+
+    /// A type with the given shape, grouping sums and products to the right.
+    fn unshape(&mut self, shape: Shape) -> Result<TypeId, BasicError> {
+        let (shapes, op): (_, fn(TypeId, TypeId) -> Type) = match shape {
+            Shape::Atom(t) => return Ok(t),
+            Shape::Sum(terms) => (terms, |left, right| Type::Sum { left, right }),
+            Shape::Prod(factors) => (factors, |fst, snd| Type::Prod { fst, snd }),
+        };
+        let mut ids = (shapes.into_iter())
+            .map(|shape| self.unshape(shape))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(mut t) = ids.pop() else {
+            return self.make(Type::Unit);
+        };
+        while let Some(prev) = ids.pop() {
+            t = self.make(op(prev, t))?;
+        }
+        Ok(t)
+    }
+
+    fn unify_shapes(&mut self, s1: Shape, s2: Shape) -> Result<(), BasicError> {
+        match (s1, s2) {
+            (Shape::Atom(t1), Shape::Atom(t2)) => self.unify(t1, t2).map(|_| ()),
+            (Shape::Atom(t), shape) | (shape, Shape::Atom(t))
+                if matches!(self.get(t), Type::Unknown { id: _ }) =>
+            {
+                let other = self.unshape(shape)?;
+                self.unify(t, other).map(|_| ())
+            }
+            (Shape::Sum(a), Shape::Sum(b)) => self.unify_each(a, b, Shape::Sum),
+            (Shape::Prod(a), Shape::Prod(b)) => self.unify_each(a, b, Shape::Prod),
+            _ => Err(BasicError::FailedToUnify),
+        }
+    }
+
+    /// Unify the terms or factors of two index types in order, letting the last one on the shorter
+    /// side stand for all the rest on the longer side.
+    fn unify_each(
+        &mut self,
+        mut a: Vec<Shape>,
+        mut b: Vec<Shape>,
+        make: fn(Vec<Shape>) -> Shape,
+    ) -> Result<(), BasicError> {
+        if a.len() > b.len() {
+            std::mem::swap(&mut a, &mut b);
+        }
+        if a.len() < b.len() {
+            if a.is_empty() {
+                return Err(BasicError::FailedToUnify);
+            }
+            let rest = b.split_off(a.len() - 1);
+            b.push(make(rest));
+        }
+        for (s1, s2) in a.into_iter().zip(b) {
+            self.unify_shapes(s1, s2)?;
+        }
+        Ok(())
+    }
+
+    /// Unify two array index types, which may group their sums and products differently.
+    fn unify_index(&mut self, t1: TypeId, t2: TypeId) -> Result<TypeId, BasicError> {
+        let (t1, t2) = (self.root(t1), self.root(t2));
+        let (s1, s2) = (self.shape(t1), self.shape(t2));
+        match self.unify_shapes(s1, s2) {
+            Ok(()) => Ok(self.root(t1)),
+            Err(BasicError::FailedToUnify | BasicError::Size { .. }) => Err(BasicError::Size {
+                left: t1,
+                right: t2,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
     fn unify(&mut self, t1: TypeId, t2: TypeId) -> Result<TypeId, BasicError> {
         let (t1, t2) = (self.root(t1), self.root(t2));
         if t1 == t2 {
@@ -274,11 +449,49 @@ impl Types {
             (_, Type::Unknown { id: _ }) => t1,
             (Type::Unknown { id: _ }, _) => t2,
             (Type::Scalar { id: _ }, Type::Scalar { id: _ }) => t1,
-            (Type::Int | Type::Float, Type::Scalar { id: _ }) => t1,
-            (Type::Scalar { id: _ }, Type::Int | Type::Float) => t2,
+            (Type::IntLit { id: _ }, Type::IntLit { id: _ }) => t1,
+            (Type::FloatLit { id: _ }, Type::FloatLit { id: _ }) => t1,
+            (Type::Int | Type::Int32, Type::IntLit { id: _ }) => t1,
+            (Type::IntLit { id: _ }, Type::Int | Type::Int32) => t2,
+            (Type::Float | Type::Float32, Type::FloatLit { id: _ }) => t1,
+            (Type::FloatLit { id: _ }, Type::Float | Type::Float32) => t2,
+            (
+                Type::IntLit { id: _ }
+                | Type::FloatLit { id: _ }
+                | Type::Int
+                | Type::Int32
+                | Type::Float
+                | Type::Float32,
+                Type::Scalar { id: _ },
+            ) => t1,
+            (
+                Type::Scalar { id: _ },
+                Type::IntLit { id: _ }
+                | Type::FloatLit { id: _ }
+                | Type::Int
+                | Type::Int32
+                | Type::Float
+                | Type::Float32,
+            ) => t2,
             (Type::Vector { id: _, scalar: _ }, Type::Vector { id: _, scalar: _ }) => t1,
-            (Type::Int | Type::Float, Type::Vector { id: _, scalar }) => self.unify(t1, scalar)?,
-            (Type::Vector { id: _, scalar }, Type::Int | Type::Float) => self.unify(scalar, t2)?,
+            (
+                Type::IntLit { id: _ }
+                | Type::FloatLit { id: _ }
+                | Type::Int
+                | Type::Int32
+                | Type::Float
+                | Type::Float32,
+                Type::Vector { id: _, scalar },
+            ) => self.unify(t1, scalar)?,
+            (
+                Type::Vector { id: _, scalar },
+                Type::IntLit { id: _ }
+                | Type::FloatLit { id: _ }
+                | Type::Int
+                | Type::Int32
+                | Type::Float
+                | Type::Float32,
+            ) => self.unify(scalar, t2)?,
             (Type::Array { index, elem }, Type::Vector { id: _, scalar })
             | (Type::Vector { id: _, scalar }, Type::Array { index, elem }) => {
                 let elem = self.unify(elem, scalar)?;
@@ -322,7 +535,7 @@ impl Types {
                     elem: elem2,
                 },
             ) => {
-                let index = self.unify(index1, index2)?;
+                let index = self.unify_index(index1, index2)?;
                 let elem = self.unify(elem1, elem2)?;
                 self.make(Type::Array { index, elem })?
             }
@@ -423,8 +636,12 @@ impl Canonizer {
                 (a1 || a2, self.make(Type::Poly { var, inner }))
             }
             Type::Unit => (false, self.make(Type::Unit)),
+            Type::IntLit { id: _ } => (false, self.make(Type::Int)),
+            Type::FloatLit { id: _ } => (false, self.make(Type::Float)),
             Type::Int => (false, self.make(Type::Int)),
+            Type::Int32 => (false, self.make(Type::Int32)),
             Type::Float => (false, self.make(Type::Float)),
+            Type::Float32 => (false, self.make(Type::Float32)),
             Type::Prod { fst, snd } => {
                 let (a1, fst) = self.ty(fst);
                 let (a2, snd) = self.ty(snd);
@@ -497,6 +714,7 @@ pub struct Module {
     exprs: Vec<ValId>,
     defs: Vec<ValId>,
     exports: HashMap<String, parse::DefId>,
+    holes: HashMap<parse::ExprId, Vec<ValId>>,
 }
 
 impl Module {
@@ -504,6 +722,15 @@ impl Module {
         self.fields.get(id)
     }
 
+    pub fn field_id(&self, name: &str) -> Option<FieldId> {
+        self.fields.find(name)
+    }
+
+    /// The names of all record fields in this module or the modules it imports.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.fields.names()
+    }
+
     pub fn ty(&self, id: TypeId) -> Type {
         self.types.get(id)
     }
@@ -524,6 +751,15 @@ impl Module {
         self.exprs[id.to_usize()]
     }
 
+    /// The source of the value an expression refers to, looking through any type arguments.
+    pub fn origin(&self, id: parse::ExprId) -> Src {
+        let mut v = self.expr(id);
+        while let Src::Inst { val, ty: _ } = self.val(v).src {
+            v = val;
+        }
+        self.val(v).src
+    }
+
     pub fn def(&self, id: parse::DefId) -> ValId {
         self.defs[id.to_usize()]
     }
@@ -532,16 +768,34 @@ impl Module {
         self.exports.get(name).copied()
     }
 
+    pub fn exports(&self) -> impl Iterator<Item = (&str, parse::DefId)> {
+        self.exports.iter().map(|(name, &id)| (name.as_str(), id))
+    }
+
+    /// The holes in this module, in order.
+    pub fn holes(&self) -> Vec<parse::ExprId> {
+        let mut ids: Vec<parse::ExprId> = self.holes.keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    /// The values in scope at a hole whose types fit the type expected there, if it is a hole.
+    pub fn hole(&self, id: parse::ExprId) -> Option<&[ValId]> {
+        self.holes.get(&id).map(Vec::as_slice)
+    }
+
     fn set_expr(&mut self, id: parse::ExprId, val: ValId) {
         self.exprs[id.to_usize()] = val;
     }
 
-    fn gc(mut self) -> (Self, Vec<TypeError>) {
+    /// Drop the types that nothing refers to anymore, along with those in the error that stopped
+    /// typechecking, if any; otherwise, report any types that are still ambiguous.
+    fn gc(mut self, err: Option<TypeError>) -> (Self, Vec<TypeError>) {
         let mut canonizer = Canonizer {
             errors: vec![],
             types: HashMap::new(),
+            new_types: Types::new(self.types.limit),
             old_types: self.types,
-            new_types: Types::new(),
             old_vals: self.vals,
             new_vals: vec![],
         };
@@ -599,52 +853,206 @@ impl Module {
                 v
             })
             .collect();
+        let mut holes: Vec<(parse::ExprId, Vec<ValId>)> = self.holes.into_iter().collect();
+        holes.sort_by_key(|&(id, _)| id);
+        self.holes = holes
+            .into_iter()
+            .map(|(id, fits)| {
+                let fits = fits
+                    .into_iter()
+                    .map(|v0| {
+                        let (_, _, v) = canonizer.val(&self.defs, &self.params, v0);
+                        v
+                    })
+                    .collect();
+                (id, fits)
+            })
+            .collect();
+        let errors = match err {
+            Some(mut err) => {
+                if let Some(size) = err.size_mut() {
+                    *size = size.map(|(left, right)| {
+                        let (_, left) = canonizer.ty(left);
+                        let (_, right) = canonizer.ty(right);
+                        (left, right)
+                    });
+                }
+                vec![err]
+            }
+            None => canonizer.errors,
+        };
         self.types = canonizer.new_types;
         self.vals = canonizer.new_vals.into_iter().collect();
-        (self, canonizer.errors)
+        (self, errors)
     }
 }
 
+/// The syntax being checked when a module ran out of types or field names.
 #[derive(Clone, Copy, Debug)]
-pub enum TypeError {
-    TooManyImports,
-    TooManyFields,
-    TooManyTypes,
-    Undefined { name: TokenId },
-    Duplicate { name: TokenId },
-    Dom { name: TokenId },
-    Cod { name: TokenId },
-    Param { id: parse::ParamId },
-    Elem { id: parse::ExprId },
-    Inst { id: parse::ExprId },
-    Apply { id: parse::ExprId },
-    MapLhs { id: parse::ExprId },
-    MapRhs { id: parse::ExprId },
-    Let { id: parse::ExprId },
-    Index { id: parse::ExprId },
-    Neg { id: parse::ExprId },
-    ElemLhs { id: parse::ExprId },
-    ElemRhs { id: parse::ExprId },
-    MulLhs { id: parse::ExprId },
-    MulRhs { id: parse::ExprId },
-    DivLhs { id: parse::ExprId },
-    DivRhs { id: parse::ExprId },
-    Lambda { id: parse::ExprId },
+pub enum Site {
+    Import { name: TokenId },
     Def { id: parse::DefId },
-    AmbigParam { id: parse::ParamId },
-    AmbigTypeArgs { id: parse::ExprId },
+    Type { id: parse::TypeId },
+    Param { id: parse::ParamId },
+    Expr { id: parse::ExprId },
+}
+
+/// Array index types that differ even when regrouped, when that's why two types don't match.
+pub type SizeMismatch = (TypeId, TypeId);
+
+#[derive(Clone, Copy, Debug)]
+pub enum TypeError {
+    TooManyImports {
+        module: TokenId,
+    },
+    TooManyFields {
+        site: Option<Site>,
+    },
+    TooManyTypes {
+        site: Option<Site>,
+    },
+    Undefined {
+        name: TokenId,
+    },
+    Duplicate {
+        name: TokenId,
+    },
+    Dom {
+        name: TokenId,
+    },
+    Cod {
+        name: TokenId,
+    },
+    Param {
+        id: parse::ParamId,
+        size: Option<SizeMismatch>,
+    },
+    Elem {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    Inst {
+        id: parse::ExprId,
+    },
+    InstVal {
+        id: parse::ExprId,
+    },
+    Apply {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    MapLhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    MapRhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    Let {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    Index {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    Neg {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    ElemLhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    ElemRhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    MulLhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    MulRhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    DivLhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    DivRhs {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    Lambda {
+        id: parse::ExprId,
+        size: Option<SizeMismatch>,
+    },
+    Def {
+        id: parse::DefId,
+        size: Option<SizeMismatch>,
+    },
+    AmbigParam {
+        id: parse::ParamId,
+    },
+    AmbigTypeArgs {
+        id: parse::ExprId,
+    },
+}
+
+impl TypeError {
+    fn size_mut(&mut self) -> Option<&mut Option<SizeMismatch>> {
+        use TypeError::*;
+        match self {
+            Param { size, .. }
+            | Elem { size, .. }
+            | Apply { size, .. }
+            | MapLhs { size, .. }
+            | MapRhs { size, .. }
+            | Let { size, .. }
+            | Index { size, .. }
+            | Neg { size, .. }
+            | ElemLhs { size, .. }
+            | ElemRhs { size, .. }
+            | MulLhs { size, .. }
+            | MulRhs { size, .. }
+            | DivLhs { size, .. }
+            | DivRhs { size, .. }
+            | Lambda { size, .. }
+            | Def { size, .. } => Some(size),
+            _ => None,
+        }
+    }
+
+    /// The array index types whose sizes differ, if that's why this error happened.
+    pub fn size(mut self) -> Option<SizeMismatch> {
+        self.size_mut().and_then(|size| *size)
+    }
+    /// Point a capacity error at `site`, unless it already points at something inside it.
+    fn at(self, site: Site) -> Self {
+        match self {
+            TypeError::TooManyFields { site: None } => {
+                TypeError::TooManyFields { site: Some(site) }
+            }
+            TypeError::TooManyTypes { site: None } => TypeError::TooManyTypes { site: Some(site) },
+            err => err,
+        }
+    }
 }
 
 type TypeResult<T> = Result<T, TypeError>;
 
 #[derive(Debug)]
 struct Typer<'a> {
+    limits: Limits,
     imports: Vec<&'a Module>,
     source: &'a str,
     tokens: &'a Tokens,
     tree: &'a parse::Module,
     module: Module,
     names: HashMap<&'a str, Vec<ValId>>,
+    holes: Vec<(parse::ExprId, Vec<ValId>)>,
 }
 
 impl<'a> Typer<'a> {
@@ -658,8 +1066,8 @@ impl<'a> Typer<'a> {
 
     fn ty(&mut self, ty: Type) -> TypeResult<TypeId> {
         self.module.types.make(ty).map_err(|e| match e {
-            BasicError::TooManyTypes => TypeError::TooManyTypes,
-            BasicError::FailedToUnify => unreachable!(),
+            BasicError::TooManyTypes => TypeError::TooManyTypes { site: None },
+            BasicError::FailedToUnify | BasicError::Size { .. } => unreachable!(),
         })
     }
 
@@ -668,8 +1076,8 @@ impl<'a> Typer<'a> {
             .types
             .unknown(|id| Type::Unknown { id })
             .map_err(|e| match e {
-                BasicError::TooManyTypes => TypeError::TooManyTypes,
-                BasicError::FailedToUnify => unreachable!(),
+                BasicError::TooManyTypes => TypeError::TooManyTypes { site: None },
+                BasicError::FailedToUnify | BasicError::Size { .. } => unreachable!(),
             })
     }
 
@@ -678,8 +1086,8 @@ impl<'a> Typer<'a> {
             .types
             .unknown(|id| Type::Scalar { id })
             .map_err(|e| match e {
-                BasicError::TooManyTypes => TypeError::TooManyTypes,
-                BasicError::FailedToUnify => unreachable!(),
+                BasicError::TooManyTypes => TypeError::TooManyTypes { site: None },
+                BasicError::FailedToUnify | BasicError::Size { .. } => unreachable!(),
             })
     }
 
@@ -688,11 +1096,18 @@ impl<'a> Typer<'a> {
             .types
             .unknown(|id| Type::Vector { id, scalar })
             .map_err(|e| match e {
-                BasicError::TooManyTypes => TypeError::TooManyTypes,
-                BasicError::FailedToUnify => unreachable!(),
+                BasicError::TooManyTypes => TypeError::TooManyTypes { site: None },
+                BasicError::FailedToUnify | BasicError::Size { .. } => unreachable!(),
             })
     }
 
+    fn literal(&mut self, f: impl FnOnce(UnknownId) -> Type) -> TypeResult<TypeId> {
+        self.module.types.unknown(f).map_err(|e| match e {
+            BasicError::TooManyTypes => TypeError::TooManyTypes { site: None },
+            BasicError::FailedToUnify | BasicError::Size { .. } => unreachable!(),
+        })
+    }
+
     fn root(&mut self, ty: TypeId) -> TypeId {
         self.module.types.root(ty)
     }
@@ -701,23 +1116,28 @@ impl<'a> Typer<'a> {
         &mut self,
         t1: TypeId,
         t2: TypeId,
-        err: impl FnOnce() -> TypeError,
+        err: impl FnOnce(Option<SizeMismatch>) -> TypeError,
     ) -> TypeResult<TypeId> {
         self.module.types.unify(t1, t2).map_err(|e| match e {
-            BasicError::TooManyTypes => TypeError::TooManyTypes,
-            BasicError::FailedToUnify => err(),
+            BasicError::TooManyTypes => TypeError::TooManyTypes { site: None },
+            BasicError::FailedToUnify => err(None),
+            BasicError::Size { left, right } => err(Some((left, right))),
         })
     }
 
     fn unify_assert(&mut self, t1: TypeId, t2: TypeId) -> TypeResult<TypeId> {
-        self.unify(t1, t2, || panic!("expected unification to succeed"))
+        self.unify(t1, t2, |_| panic!("expected unification to succeed"))
     }
 
-    fn unknowns<T>(&mut self, a: &[T], mut f: impl FnMut(usize) -> Src) -> TypeResult<Vec<ValId>> {
+    fn unknowns<T>(
+        &mut self,
+        a: &[T],
+        mut f: impl FnMut(usize) -> (Src, Site),
+    ) -> TypeResult<Vec<ValId>> {
         (0..a.len())
             .map(|i| {
-                let ty = self.unknown()?;
-                let src = f(i);
+                let (src, site) = f(i);
+                let ty = self.unknown().map_err(|e| e.at(site))?;
                 Ok(self.val(Val { ty, src }))
             })
             .collect()
@@ -727,9 +1147,13 @@ impl<'a> Typer<'a> {
         match self.module.ty(inner) {
             Type::Unknown { id: _ }
             | Type::Scalar { id: _ }
+            | Type::IntLit { id: _ }
+            | Type::FloatLit { id: _ }
             | Type::Unit
             | Type::Int
+            | Type::Int32
             | Type::Float
+            | Type::Float32
             | Type::End => Ok(inner),
             Type::Vector { id, scalar } => {
                 let scalar = self.sub(var, scalar, ty)?;
@@ -801,14 +1225,25 @@ impl<'a> Typer<'a> {
         &mut self,
         types: &IndexMap<&'a str, TypeId>,
         id: parse::TypeId,
+    ) -> TypeResult<TypeId> {
+        self.parse_ty_inner(types, id)
+            .map_err(|e| e.at(Site::Type { id }))
+    }
+
+    fn parse_ty_inner(
+        &mut self,
+        types: &IndexMap<&'a str, TypeId>,
+        id: parse::TypeId,
     ) -> TypeResult<TypeId> {
         let unknown = self.module.parsed_ty(id);
         let actual = match self.tree.ty(id) {
             parse::Type::Paren { inner } => self.parse_ty(types, inner),
             parse::Type::Unit { open: _, close: _ } => self.ty(Type::Unit),
             parse::Type::Name { name } => match self.token(name) {
-                "Int" => self.ty(Type::Int),
-                "Float" => self.ty(Type::Float),
+                "Int" | "Int64" => self.ty(Type::Int),
+                "Int32" => self.ty(Type::Int32),
+                "Float" | "Float64" => self.ty(Type::Float),
+                "Float32" => self.ty(Type::Float32),
                 s => types.get(s).ok_or(TypeError::Undefined { name }).copied(),
             },
             parse::Type::Prod { fst, snd } => {
@@ -844,6 +1279,17 @@ impl<'a> Typer<'a> {
         names: &mut Vec<&'a str>,
         strict: bool,
         id: parse::ParamId,
+    ) -> TypeResult<TypeId> {
+        self.param_inner(types, names, strict, id)
+            .map_err(|e| e.at(Site::Param { id }))
+    }
+
+    fn param_inner(
+        &mut self,
+        types: &IndexMap<&'a str, TypeId>,
+        names: &mut Vec<&'a str>,
+        strict: bool,
+        id: parse::ParamId,
     ) -> TypeResult<TypeId> {
         let parse::Param { bind, ty } = self.tree.param(id);
         let val = self.module.param(id);
@@ -898,7 +1344,7 @@ impl<'a> Typer<'a> {
             Some(ast) => self.parse_ty(types, ast)?,
             None => unknown,
         };
-        self.unify(expected, actual, || TypeError::Param { id })
+        self.unify(expected, actual, |size| TypeError::Param { id, size })
     }
 
     fn func(
@@ -936,6 +1382,15 @@ impl<'a> Typer<'a> {
         &mut self,
         types: &mut IndexMap<&'a str, TypeId>,
         id: parse::ExprId,
+    ) -> TypeResult<TypeId> {
+        self.expr_inner(types, id)
+            .map_err(|e| e.at(Site::Expr { id }))
+    }
+
+    fn expr_inner(
+        &mut self,
+        types: &mut IndexMap<&'a str, TypeId>,
+        id: parse::ExprId,
     ) -> TypeResult<TypeId> {
         let val = self.module.expr(id);
         let unknown = self.module.val(val).ty;
@@ -957,15 +1412,24 @@ impl<'a> Typer<'a> {
                 self.unify_assert(ty, unknown)
             }
             parse::Expr::Undefined { token: _ } => Ok(unknown),
+            parse::Expr::Hole { token: _ } => {
+                let scope = self
+                    .names
+                    .values()
+                    .filter_map(|stack| stack.last().copied())
+                    .collect();
+                self.holes.push((id, scope));
+                Ok(unknown)
+            }
             parse::Expr::Unit { open: _, close: _ } => {
                 let unit = self.ty(Type::Unit)?;
                 self.unify_assert(unit, unknown)
             }
             parse::Expr::Number { val } => {
                 let ty = if self.token(val).contains('.') {
-                    self.ty(Type::Float)?
+                    self.literal(|id| Type::FloatLit { id })?
                 } else {
-                    self.ty(Type::Int)?
+                    self.literal(|id| Type::IntLit { id })?
                 };
                 self.unify_assert(ty, unknown)
             }
@@ -1012,12 +1476,10 @@ impl<'a> Typer<'a> {
                 let array = self.expr(types, array)?;
                 let elem = unknown;
                 let expected = self.ty(Type::Array { index, elem })?;
-                self.unify(expected, array, || TypeError::Elem { id })?;
+                self.unify(expected, array, |size| TypeError::Elem { id, size })?;
                 Ok(elem)
             }
-            parse::Expr::Inst { val: _, ty: _ } => {
-                panic!("polymorphic instantiation should always be inside function application")
-            }
+            parse::Expr::Inst { val: _, ty: _ } => Err(TypeError::InstVal { id }),
             parse::Expr::Apply { mut func, arg } => {
                 let inst = func;
                 let mut type_args = vec![];
@@ -1040,15 +1502,12 @@ impl<'a> Typer<'a> {
                 let dom = self.expr(types, arg)?;
                 let cod = unknown;
                 let expected = self.ty(Type::Func { dom, cod })?;
-                self.unify(expected, fty, || TypeError::Apply { id })?;
+                self.unify(expected, fty, |size| TypeError::Apply { id, size })?;
                 let inst_ty = self.module.val(self.module.expr(inst)).ty;
                 self.unify_assert(inst_ty, fty)?; // be sure to always mark type of outermost `Inst`
                 Ok(cod)
             }
             parse::Expr::Map { func, arg } => {
-                if let parse::Expr::Inst { val: _, ty: _ } = self.tree.expr(func) {
-                    panic!("dot application can't have explicit type arguments");
-                }
                 let fty = self.func(types, &mut vec![], func)?;
                 let aty = self.expr(types, arg)?;
                 let dom = self.unknown()?;
@@ -1056,8 +1515,8 @@ impl<'a> Typer<'a> {
                 let index = self.unknown()?;
                 let f = self.ty(Type::Func { dom, cod })?;
                 let a = self.ty(Type::Array { index, elem: dom })?;
-                self.unify(f, fty, || TypeError::MapLhs { id })?;
-                self.unify(a, aty, || TypeError::MapRhs { id })?;
+                self.unify(f, fty, |size| TypeError::MapLhs { id, size })?;
+                self.unify(a, aty, |size| TypeError::MapRhs { id, size })?;
                 let b = self.ty(Type::Array { index, elem: cod })?;
                 self.unify_assert(b, unknown)
             }
@@ -1067,7 +1526,7 @@ impl<'a> Typer<'a> {
                     types,
                     |this, types, names| {
                         let expected = this.param(types, names, false, param)?;
-                        this.unify(expected, actual, || TypeError::Let { id })?;
+                        this.unify(expected, actual, |size| TypeError::Let { id, size })?;
                         Ok(())
                     },
                     |this, types| this.expr(types, body),
@@ -1077,7 +1536,7 @@ impl<'a> Typer<'a> {
             parse::Expr::Index { name, val, body } => {
                 let expected = self.ty(Type::Int)?;
                 let actual = self.expr(types, val)?;
-                self.unify(expected, actual, || TypeError::Index { id })?;
+                self.unify(expected, actual, |size| TypeError::Index { id, size })?;
                 let s = self.token(name);
                 let t = self.ty(Type::Var {
                     src: None,
@@ -1095,7 +1554,7 @@ impl<'a> Typer<'a> {
                     let arg = self.expr(types, arg)?;
                     let scalar = self.scalar()?;
                     let vector = self.vector(scalar)?;
-                    self.unify(vector, arg, || TypeError::Neg { id })?;
+                    self.unify(vector, arg, |size| TypeError::Neg { id, size })?;
                     self.unify_assert(vector, unknown)
                 }
             },
@@ -1108,8 +1567,8 @@ impl<'a> Typer<'a> {
                     let right = self.expr(types, rhs)?;
                     let scalar = self.scalar()?;
                     let vector = self.vector(scalar)?;
-                    self.unify(vector, left, || TypeError::ElemLhs { id })?;
-                    self.unify(vector, right, || TypeError::ElemRhs { id })?;
+                    self.unify(vector, left, |size| TypeError::ElemLhs { id, size })?;
+                    self.unify(vector, right, |size| TypeError::ElemRhs { id, size })?;
                     self.unify_assert(vector, unknown)
                 }
                 parse::Binop::Mul => {
@@ -1117,8 +1576,8 @@ impl<'a> Typer<'a> {
                     let right = self.expr(types, rhs)?;
                     let scalar = self.scalar()?;
                     let vector = self.vector(scalar)?;
-                    self.unify(scalar, left, || TypeError::MulLhs { id })?;
-                    self.unify(vector, right, || TypeError::MulRhs { id })?;
+                    self.unify(scalar, left, |size| TypeError::MulLhs { id, size })?;
+                    self.unify(vector, right, |size| TypeError::MulRhs { id, size })?;
                     self.unify_assert(vector, unknown)
                 }
                 parse::Binop::Div => {
@@ -1126,8 +1585,8 @@ impl<'a> Typer<'a> {
                     let right = self.expr(types, rhs)?;
                     let scalar = self.scalar()?;
                     let vector = self.vector(scalar)?;
-                    self.unify(vector, left, || TypeError::DivLhs { id })?;
-                    self.unify(scalar, right, || TypeError::DivRhs { id })?;
+                    self.unify(vector, left, |size| TypeError::DivLhs { id, size })?;
+                    self.unify(scalar, right, |size| TypeError::DivRhs { id, size })?;
                     self.unify_assert(vector, unknown)
                 }
             },
@@ -1141,7 +1600,7 @@ impl<'a> Typer<'a> {
                             Some(t) => this.parse_ty(types, t)?,
                             None => actual,
                         };
-                        this.unify(expected, actual, || TypeError::Lambda { id })
+                        this.unify(expected, actual, |size| TypeError::Lambda { id, size })
                     },
                 )?;
                 let fty = self.ty(Type::Func { dom, cod })?;
@@ -1173,7 +1632,9 @@ impl<'a> Typer<'a> {
         let t = match import.ty(t0) {
             Type::Unknown { id: _ }
             | Type::Scalar { id: _ }
-            | Type::Vector { id: _, scalar: _ } => {
+            | Type::Vector { id: _, scalar: _ }
+            | Type::IntLit { id: _ }
+            | Type::FloatLit { id: _ } => {
                 panic!("unresolved type from import")
             }
             Type::Fragment => panic!("fragment type from import"),
@@ -1188,7 +1649,9 @@ impl<'a> Typer<'a> {
             }
             Type::Unit => self.ty(Type::Unit)?,
             Type::Int => self.ty(Type::Int)?,
+            Type::Int32 => self.ty(Type::Int32)?,
             Type::Float => self.ty(Type::Float)?,
+            Type::Float32 => self.ty(Type::Float32)?,
             Type::Prod { fst, snd } => {
                 let fst = self.translate(i, ids, fst)?;
                 let snd = self.translate(i, ids, snd)?;
@@ -1222,21 +1685,20 @@ impl<'a> Typer<'a> {
     }
 
     fn imports(&mut self) -> TypeResult<()> {
-        let imports = self.tree.imports();
-        let n = imports.len();
-        if n == 0 {
-            return Ok(()); // avoid underflow when we decrement below
-        }
-        for index in 0..=(n - 1).try_into().map_err(|_| TypeError::TooManyImports)? {
-            let src = ImportId { index };
-            let module = self.imports[src.to_usize()];
+        for (i, import) in self.tree.imports().iter().enumerate() {
+            let src = capped(i, self.limits.imports).ok_or(TypeError::TooManyImports {
+                module: import.module,
+            })?;
+            let module = self.imports[i];
             let mut translated = HashMap::new();
-            for &token in imports[src.to_usize()].names.iter() {
+            for &token in import.names.iter() {
                 let id = module
                     .export(self.token(token))
                     .ok_or(TypeError::Undefined { name: token })?;
                 let val = module.def(id);
-                let ty = self.translate(src, &mut translated, module.val(val).ty)?;
+                let ty = self
+                    .translate(src, &mut translated, module.val(val).ty)
+                    .map_err(|e| e.at(Site::Import { name: token }))?;
                 let val = self.val(Val {
                     ty,
                     src: Src::Import { src, id },
@@ -1252,13 +1714,19 @@ impl<'a> Typer<'a> {
             .tree
             .types()
             .iter()
-            .map(|_| self.unknown())
+            .enumerate()
+            .map(|(i, _)| {
+                let id = parse::TypeId::from_usize(i).unwrap();
+                self.unknown().map_err(|e| e.at(Site::Type { id }))
+            })
             .collect::<TypeResult<Vec<TypeId>>>()?;
-        self.module.params = self.unknowns(self.tree.params(), |i| Src::Param {
-            id: parse::ParamId::from_usize(i).unwrap(),
+        self.module.params = self.unknowns(self.tree.params(), |i| {
+            let id = parse::ParamId::from_usize(i).unwrap();
+            (Src::Param { id }, Site::Param { id })
         })?;
-        self.module.exprs = self.unknowns(self.tree.exprs(), |i| Src::Expr {
-            id: parse::ExprId::from_usize(i).unwrap(),
+        self.module.exprs = self.unknowns(self.tree.exprs(), |i| {
+            let id = parse::ExprId::from_usize(i).unwrap();
+            (Src::Expr { id }, Site::Expr { id })
         })?;
         self.imports()?;
         let defs = self
@@ -1274,10 +1742,12 @@ impl<'a> Typer<'a> {
                     ty,
                     body: _,
                 } = def;
+                let id = parse::DefId::from_usize(i).unwrap();
                 let names = types
                     .iter()
                     .map(|&def| Ok((self.token(def), self.ty(Type::Var { src: None, def })?)))
-                    .collect::<TypeResult<IndexMap<&'a str, TypeId>>>()?;
+                    .collect::<TypeResult<IndexMap<&'a str, TypeId>>>()
+                    .map_err(|e| e.at(Site::Def { id }))?;
                 let (doms, ()) = self.scope(
                     &names,
                     |this, names, temps| {
@@ -1289,12 +1759,15 @@ impl<'a> Typer<'a> {
                     |_, _| Ok(()),
                 )?;
                 let cod = self.parse_ty(&names, ty.ok_or(TypeError::Cod { name: *name })?)?;
-                let t = names.values().try_rfold(
-                    doms.into_iter()
-                        .try_rfold(cod, |cod, dom| self.ty(Type::Func { dom, cod }))?,
-                    |inner, &var| self.ty(Type::Poly { var, inner }),
-                )?;
-                let id = parse::DefId::from_usize(i).unwrap();
+                let t = doms
+                    .into_iter()
+                    .try_rfold(cod, |cod, dom| self.ty(Type::Func { dom, cod }))
+                    .and_then(|t| {
+                        names
+                            .values()
+                            .try_rfold(t, |inner, &var| self.ty(Type::Poly { var, inner }))
+                    })
+                    .map_err(|e| e.at(Site::Def { id }))?;
                 let src = Src::Def { id };
                 let val = self.val(Val { ty: t, src });
                 self.module.defs.push(val);
@@ -1324,12 +1797,43 @@ impl<'a> Typer<'a> {
                 },
                 |this, mut types| {
                     let actual = this.expr(&mut types, *body)?;
-                    this.unify(expected, actual, || TypeError::Def { id })
+                    this.unify(expected, actual, |size| TypeError::Def { id, size })
+                        .map_err(|e| e.at(Site::Def { id }))
                 },
             )?;
         }
+        for (id, scope) in take(&mut self.holes) {
+            let expected = self.module.val(self.module.expr(id)).ty;
+            let mut fits = vec![];
+            for val in scope {
+                if self
+                    .fits(expected, val)
+                    .map_err(|e| e.at(Site::Expr { id }))?
+                {
+                    fits.push(val);
+                }
+            }
+            self.module.holes.insert(id, fits);
+        }
         Ok(())
     }
+
+    fn fits(&mut self, expected: TypeId, val: ValId) -> TypeResult<bool> {
+        // unification is destructive, so we try it on a copy and then throw that copy away
+        let types = self.module.types.clone();
+        let mut ty = self.root(self.module.val(val).ty);
+        while let Type::Poly { var, inner } = self.module.ty(ty) {
+            let t = self.unknown()?;
+            ty = self.sub(var, inner, t)?;
+        }
+        let res = self.module.types.unify(expected, ty);
+        self.module.types = types;
+        match res {
+            Ok(_) => Ok(true),
+            Err(BasicError::TooManyTypes) => Err(TypeError::TooManyTypes { site: None }),
+            Err(BasicError::FailedToUnify | BasicError::Size { .. }) => Ok(false),
+        }
+    }
 }
 
 pub fn typecheck(
@@ -1337,30 +1841,41 @@ pub fn typecheck(
     tokens: &Tokens,
     tree: &parse::Module,
     imports: Vec<&Module>,
+) -> (Module, Vec<TypeError>) {
+    typecheck_limited(source, tokens, tree, imports, Limits::MAX)
+}
+
+/// Typecheck a module that runs out of IDs sooner than usual, which lets tests reach those errors
+/// without building huge modules.
+fn typecheck_limited(
+    source: &str,
+    tokens: &Tokens,
+    tree: &parse::Module,
+    imports: Vec<&Module>,
+    limits: Limits,
 ) -> (Module, Vec<TypeError>) {
     let mut typer = Typer {
+        limits,
         imports,
         source,
         tokens,
         tree,
         module: Module {
-            fields: Fields::new(),
-            types: Types::new(),
+            fields: Fields::new(limits.fields),
+            types: Types::new(limits.types),
             parsed_types: vec![],
             vals: vec![],
             params: vec![],
             exprs: vec![],
             defs: vec![],
             exports: HashMap::new(),
+            holes: HashMap::new(),
         },
         names: HashMap::new(),
+        holes: vec![],
     };
     let res = typer.module();
-    let (module, errors) = typer.module.gc();
-    let errs = match res {
-        Ok(()) => errors,
-        Err(e) => vec![e],
-    };
+    let (module, errs) = typer.module.gc(res.err());
     if module.types.unknowns > 0 {
         assert!(!errs.is_empty(), "ambiguous types should cause errors");
     }
@@ -1376,6 +1891,7 @@ mod tests {
 
     use crate::{
         compile::{FullModule, Importer, Printer},
+        fetch::builtin,
         lex::lex,
         parse::parse,
         util::{Diagnostic, Emitter},
@@ -1383,19 +1899,68 @@ mod tests {
 
     use super::*;
 
+    /// A standard library module, since those are the only ones that test files can import.
+    #[derive(Debug)]
+    struct Builtin {
+        path: String,
+        source: &'static str,
+        tokens: Tokens,
+        tree: parse::Module,
+        module: Arc<Module>,
+    }
+
+    impl Builtin {
+        fn new(name: &str) -> Self {
+            let path = format!("{name}.adroit");
+            let source = builtin(Path::new(&path)).expect(name);
+            let tokens = lex(source).expect(name);
+            let tree = parse(&tokens).expect(name);
+            let (module, errors) = typecheck(source, &tokens, &tree, vec![]);
+            assert!(errors.is_empty(), "{name}");
+            Self {
+                path,
+                source,
+                tokens,
+                tree,
+                module: Arc::new(module),
+            }
+        }
+    }
+
     #[derive(Clone, Copy, Debug)]
-    struct NoImports;
+    struct Builtins<'a> {
+        modules: &'a [Builtin],
+    }
+
+    impl<'a> Importer<'a> for Builtins<'a> {
+        fn import(&self, id: ImportId) -> Option<FullModule<'a>> {
+            let Builtin {
+                path: _,
+                source,
+                tokens,
+                tree,
+                module,
+            } = &self.modules[id.to_usize()];
+            Some(FullModule {
+                source,
+                tokens,
+                tree,
+                module: Arc::clone(module),
+            })
+        }
 
-    impl Importer for NoImports {
-        fn import(&self, _: ImportId) -> FullModule {
-            unimplemented!()
+        fn path(&self, id: ImportId) -> &'a str {
+            &self.modules[id.to_usize()].path
         }
     }
 
+    /// Spans in imported modules are written as `path:line:col` on the line of the diagnostic.
     #[derive(Debug)]
     struct LineEmitter<'a> {
         path: &'a str,
         index: LineIndex,
+        imports: HashMap<&'a str, LineIndex>,
+        last: LineCol,
         errors: HashMap<usize, Vec<(u32, u32, String)>>,
     }
 
@@ -1404,8 +1969,28 @@ mod tests {
             self.index.line_col(TextSize::new(i.try_into().unwrap()))
         }
 
+        /// Add a message without carets, aligned with the most recent diagnostic.
+        fn annotate(&mut self, message: String) {
+            let LineCol { line, col } = self.last;
+            let col = col.saturating_sub(1);
+            self.errors
+                .entry(u32_to_usize(line))
+                .or_default()
+                .push((col, col, message));
+        }
+
         fn emit(&mut self, (path, range): (&'a str, Range<usize>), message: impl ToString) {
-            assert_eq!(path, self.path);
+            if path != self.path {
+                let LineCol { line, col } =
+                    self.imports[path].line_col(TextSize::new(range.start.try_into().unwrap()));
+                self.annotate(format!(
+                    "{path}:{}:{}: {}",
+                    line + 1,
+                    col + 1,
+                    message.to_string()
+                ));
+                return;
+            }
             let start = self.line_col(range.start);
             let end = self.line_col(range.end);
             self.errors
@@ -1429,9 +2014,18 @@ mod tests {
             span: (&'a str, Range<usize>),
             message: impl ToString,
         ) -> impl Diagnostic<(&'a str, Range<usize>)> {
+            self.last = self.line_col(span.1.start);
             self.emit(span, message);
             LineDiagnostic { emitter: self }
         }
+
+        fn info(
+            &mut self,
+            span: (&'a str, Range<usize>),
+            message: impl ToString,
+        ) -> impl Diagnostic<(&'a str, Range<usize>)> {
+            self.diagnostic(span, message)
+        }
     }
 
     #[derive(Debug)]
@@ -1445,9 +2039,22 @@ mod tests {
             self
         }
 
+        fn note(self, message: impl ToString) -> Self {
+            self.emitter
+                .annotate(format!("note: {}", message.to_string()));
+            self
+        }
+
         fn finish(self) {}
     }
 
+    /// Small enough for test files to run out of each kind of ID.
+    const LIMITS: Limits = Limits {
+        fields: 8,
+        types: 512,
+        imports: 8,
+    };
+
     #[test]
     fn test_errors() {
         let prefix = Path::new("src/typecheck/errors");
@@ -1464,12 +2071,23 @@ mod tests {
             );
             let tokens = lex(&source).expect(stripped);
             let tree = parse(&tokens).expect(stripped);
-            let (module, errors) = typecheck(&source, &tokens, &tree, vec![]);
+            let builtins: Vec<Builtin> = tree
+                .imports()
+                .iter()
+                .map(|import| Builtin::new(&tokens.get(import.module).string(&source)))
+                .collect();
+            let imports = builtins.iter().map(|b| b.module.as_ref()).collect();
+            let (module, errors) = typecheck_limited(&source, &tokens, &tree, imports, LIMITS);
 
             let path_str: &str = &path.display().to_string();
             let mut emitter = LineEmitter {
                 path: path_str,
                 index: LineIndex::new(&source),
+                imports: builtins
+                    .iter()
+                    .map(|b| (b.path.as_str(), LineIndex::new(b.source)))
+                    .collect(),
+                last: LineCol { line: 0, col: 0 },
                 errors: HashMap::new(),
             };
             let full = FullModule {
@@ -1478,10 +2096,11 @@ mod tests {
                 tree: &tree,
                 module: Arc::new(module),
             };
-            let printer = Printer::new(full, NoImports);
+            let printer = Printer::new(full, Builtins { modules: &builtins });
             for error in errors {
                 printer.emit_type_error(&mut emitter, path_str, error);
             }
+            printer.emit_holes(&mut emitter, path_str);
 
             let mut file = mint.new_goldenfile(stripped).expect(stripped);
             for (i, line) in source.lines().enumerate() {
@@ -1501,4 +2120,42 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_size_note() {
+        let source = "def bad[N](a: [N]Float): [N * N]Float = a\n\ndef id(x: Float): Float = x\n";
+        let tokens = lex(source).unwrap();
+        let tree = parse(&tokens).unwrap();
+        let (module, mut errors) = typecheck(source, &tokens, &tree, vec![]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].size().is_some());
+        // checking stops at the first error, so the unrelated one has to be made up
+        let name = tree.def(parse::DefId::from_usize(1).unwrap()).name;
+        errors.push(TypeError::Duplicate { name });
+
+        let mut emitter = LineEmitter {
+            path: "a.adroit",
+            index: LineIndex::new(source),
+            imports: HashMap::new(),
+            last: LineCol { line: 0, col: 0 },
+            errors: HashMap::new(),
+        };
+        let full = FullModule {
+            source,
+            tokens: &tokens,
+            tree: &tree,
+            module: Arc::new(module),
+        };
+        let printer = Printer::new(full, Builtins { modules: &[] });
+        for error in errors {
+            printer.emit_type_error(&mut emitter, "a.adroit", error);
+        }
+        let notes = |line| {
+            (emitter.errors[&line].iter())
+                .filter(|(_, _, message)| message.starts_with("note: "))
+                .count()
+        };
+        assert_eq!(notes(0), 1);
+        assert_eq!(notes(2), 0);
+    }
 }
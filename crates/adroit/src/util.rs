@@ -26,9 +26,15 @@ pub trait Id: Sized {
 pub trait Diagnostic<S> {
     fn related(self, span: S, message: impl ToString) -> Self;
 
+    /// Attach a message that doesn't refer to any particular span.
+    fn note(self, message: impl ToString) -> Self;
+
     fn finish(self);
 }
 
 pub trait Emitter<S> {
     fn diagnostic(&mut self, span: S, message: impl ToString) -> impl Diagnostic<S>;
+
+    /// Like `diagnostic`, but for messages that are informative rather than a problem to be fixed.
+    fn info(&mut self, span: S, message: impl ToString) -> impl Diagnostic<S>;
 }
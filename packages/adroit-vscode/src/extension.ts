@@ -18,7 +18,25 @@ export const activate = (context: vscode.ExtensionContext): void => {
     "adroit",
     "Adroit",
     { command, args: ["lsp"] },
-    { documentSelector: ["adroit"] },
+    {
+      documentSelector: ["adroit"],
+      synchronize: { configurationSection: "adroit" },
+    },
+  );
+  // code lenses send LSP types, but the built-in command needs VS Code types
+  context.subscriptions.push(
+    vscode.commands.registerCommand(
+      "adroit.showReferences",
+      (uri: string, position: lsp.Position, locations: lsp.Location[]) => {
+        const p2c = client.protocol2CodeConverter;
+        return vscode.commands.executeCommand(
+          "editor.action.showReferences",
+          p2c.asUri(uri),
+          p2c.asPosition(position),
+          locations.map((location) => p2c.asLocation(location)),
+        );
+      },
+    ),
   );
   client.start();
 };